@@ -24,10 +24,6 @@ pub trait Encoder {
 
     fn encode<IN: AsRef<[u8]>>(encoded: &mut [u8], bin: IN) -> Result<&[u8], Error>;
 
-    fn encode_to_str<IN: AsRef<[u8]>>(encoded: &mut [u8], bin: IN) -> Result<&str, Error> {
-        Ok(core::str::from_utf8(Self::encode(encoded, bin)?).unwrap())
-    }
-
     fn encode_to_string<IN: AsRef<[u8]>>(bin: IN) -> Result<String, Error> {
         let mut encoded = vec![0u8; Self::encoded_len(bin.as_ref().len())?];
         let encoded_len = Self::encode(&mut encoded, bin)?.len();
@@ -43,15 +43,33 @@ pub const SIG_DEFAULT_JWK_FILE: &str = "jwk.json";
 /// The default suffix for signatures.
 pub const SIG_SUFFIX: &str = ".minisig";
 
+/// The default length of an onion service time period, in minutes.
+pub const ONION_TIME_PERIOD_LENGTH: u64 = 1440;
+
 pub(crate) const CHK_ALG: [u8; 2] = *b"B2";
 pub(crate) const CHK_BYTES: usize = 32;
 pub(crate) const COMMENT_PREFIX: &str = "untrusted comment: ";
+pub(crate) const DID_KEY_PREFIX: &str = "did:key:";
 pub(crate) const KDF_ALG: [u8; 2] = *b"Sc";
+pub(crate) const KDF_ALG_ARGON2ID: [u8; 2] = *b"Ar";
+pub(crate) const KDF_NONE: [u8; 2] = [0u8; 2];
 pub(crate) const KDF_SALTBYTES: usize = 32;
 pub(crate) const KEYNUM_BYTES: usize = 8;
 pub(crate) const MEMLIMIT: usize = 33_554_432;
+pub(crate) const ONION_BASEPOINT_STR: &str = "(15112221349535400772501151409588531511454012693041857206046113283949847762202, 46316835694926478169428394003475163141307993866256225615783033603165251855960)";
+pub(crate) const ONION_BLIND_STRING: &[u8] = b"Derive temporary signing key\0";
 pub(crate) const OPSLIMIT: u64 = 1_048_576;
+pub(crate) const MEMLIMIT_INTERACTIVE: usize = 16_777_216;
+pub(crate) const OPSLIMIT_INTERACTIVE: u64 = 524_288;
+pub(crate) const MEMLIMIT_SENSITIVE: usize = 1_073_741_824;
+pub(crate) const OPSLIMIT_SENSITIVE: u64 = 33_554_432;
 pub(crate) const MEMLIMIT_MAX: usize = 1_073_741_824;
+pub(crate) const ARGON2ID_MEMLIMIT_INTERACTIVE: usize = 67_108_864;
+pub(crate) const ARGON2ID_OPSLIMIT_INTERACTIVE: u64 = 2;
+pub(crate) const ARGON2ID_MEMLIMIT_MODERATE: usize = 268_435_456;
+pub(crate) const ARGON2ID_OPSLIMIT_MODERATE: u64 = 3;
+pub(crate) const ARGON2ID_MEMLIMIT_SENSITIVE: usize = 1_073_741_824;
+pub(crate) const ARGON2ID_OPSLIMIT_SENSITIVE: u64 = 4;
 pub(crate) const N_LOG2_MAX: u8 = 20;
 pub(crate) const PASSWORD_MAXBYTES: usize = 1024;
 pub(crate) const PK_B64_ENCODED_LEN: usize = 56;
@@ -61,7 +79,10 @@ pub(crate) const SECRETKEY_BYTES: usize = 64;
 pub(crate) const SECRETKEY_DEFAULT_COMMENT: &str = "rsign encrypted secret key";
 pub(crate) const SIGALG_PREHASHED: [u8; 2] = *b"ED";
 pub(crate) const SIGALG: [u8; 2] = *b"Ed";
+pub(crate) const SIGALG_EXPANDED: [u8; 2] = *b"EX";
 pub(crate) const SIGNATURE_BYTES: usize = 64;
+pub(crate) const TOR_PUBLICKEY_HEADER: &[u8; 32] = b"== ed25519v1-public: type0 ==\0\0\0";
+pub(crate) const TOR_SECRETKEY_HEADER: &[u8; 32] = b"== ed25519v1-secret: type0 ==\0\0\0";
 pub(crate) const TRUSTED_COMMENT_PREFIX_LEN: usize = 17;
 pub(crate) const TRUSTED_COMMENT_PREFIX: &str = "trusted comment: ";
 pub(crate) const TWOBYTES: usize = 2;
@@ -0,0 +1,470 @@
+use crate::crypto::ed25519;
+use crate::did::*;
+use crate::errors::*;
+use crate::helpers::*;
+use crate::secret_key::*;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+
+const CREDENTIALS_V1_CONTEXT: &str = "https://www.w3.org/2018/credentials/v1";
+const CREDENTIALS_V2_CONTEXT: &str = "https://www.w3.org/ns/credentials/v2";
+const DATA_INTEGRITY_CONTEXT: &str = "https://w3id.org/security/data-integrity/v2";
+const PROOF_TYPE: &str = "DataIntegrityProof";
+const CRYPTOSUITE: &str = "eddsa-jcs-2022";
+
+/// Format a number like ECMAScript's `Number.prototype.toString()`, as RFC 8785 requires.
+fn jcs_number(x: f64) -> String {
+    if x == 0.0 {
+        return "0".to_string();
+    }
+    if x < 0.0 {
+        return format!("-{}", jcs_number(-x));
+    }
+    // the shortest digits that round-trip, and the exponent of the first one
+    let sci = format!("{:e}", x);
+    let (mantissa, exp) = sci.split_once('e').unwrap();
+    let digits = mantissa.replace('.', "");
+    let (k, n) = (digits.len() as i32, exp.parse::<i32>().unwrap() + 1);
+    if k <= n && n <= 21 {
+        format!("{}{}", digits, "0".repeat((n - k) as usize))
+    } else if 0 < n && n <= 21 {
+        format!("{}.{}", &digits[..n as usize], &digits[n as usize..])
+    } else if -6 < n && n <= 0 {
+        format!("0.{}{}", "0".repeat(-n as usize), digits)
+    } else {
+        let sign = if n > 0 { "+" } else { "-" };
+        let fraction = if k > 1 {
+            format!(".{}", &digits[1..])
+        } else {
+            String::new()
+        };
+        format!("{}{}e{}{}", &digits[..1], fraction, sign, (n - 1).abs())
+    }
+}
+
+/// The JSON Canonicalization Scheme (RFC 8785) serialization of `value`.
+pub(crate) fn jcs(value: &Value) -> String {
+    match value {
+        Value::Number(number) => jcs_number(number.as_f64().unwrap_or(f64::NAN)),
+        Value::Array(values) => {
+            let values: Vec<String> = values.iter().map(jcs).collect();
+            format!("[{}]", values.join(","))
+        }
+        Value::Object(members) => {
+            let mut members: Vec<(&String, &Value)> = members.iter().collect();
+            // keys are sorted by their UTF-16 code units
+            members.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+            let members: Vec<String> = members
+                .into_iter()
+                .map(|(key, value)| format!("{}:{}", Value::String(key.clone()), jcs(value)))
+                .collect();
+            format!("{{{}}}", members.join(","))
+        }
+        // strings are escaped the same way as by serde_json
+        _ => value.to_string(),
+    }
+}
+
+fn proof_str<'a>(proof: &'a Map<String, Value>, name: &'static str) -> Result<&'a str> {
+    match proof.get(name) {
+        Some(Value::String(value)) => Ok(value),
+        Some(_) => Err(PError::InvalidProof("properties must be strings")),
+        None => Err(PError::MissingField(name)),
+    }
+}
+
+fn contexts(context: Option<&Value>) -> Vec<&str> {
+    match context {
+        Some(Value::String(context)) => vec![context.as_str()],
+        Some(Value::Array(contexts)) => contexts.iter().filter_map(Value::as_str).collect(),
+        _ => vec![],
+    }
+}
+
+fn has_type(document: &Value, expected: &str) -> bool {
+    match document.get("type") {
+        Some(Value::String(t)) => t == expected,
+        Some(Value::Array(types)) => types.iter().any(|t| t == expected),
+        _ => false,
+    }
+}
+
+/// Check the parts of a credential or presentation that matter for its proof.
+fn check_envelope(document: &Value, expected_type: &'static str) -> Result<()> {
+    if !document.is_object() {
+        return Err(PError::InvalidCredential("not a JSON object"));
+    }
+    match contexts(document.get("@context")).first() {
+        Some(&CREDENTIALS_V1_CONTEXT) | Some(&CREDENTIALS_V2_CONTEXT) => {}
+        _ => {
+            return Err(PError::InvalidCredential(
+                "the first context must be a credentials context",
+            ))
+        }
+    }
+    if !has_type(document, expected_type) {
+        return Err(PError::InvalidCredential("unexpected type"));
+    }
+    Ok(())
+}
+
+/// The issuer of a credential; either a string or an object with an `id`.
+fn issuer(credential: &Value) -> Result<&str> {
+    let issuer = credential
+        .get("issuer")
+        .ok_or(PError::MissingField("issuer"))?;
+    issuer
+        .as_str()
+        .or_else(|| issuer.get("id").and_then(Value::as_str))
+        .ok_or(PError::InvalidCredential("the issuer must be a URI"))
+}
+
+/// The hash signed by an `eddsa-jcs-2022` proof.
+fn hash_data(proof_config: &Value, document: &Value) -> Vec<u8> {
+    let mut hash = Sha256::digest(jcs(proof_config).as_bytes()).to_vec();
+    hash.extend_from_slice(&Sha256::digest(jcs(document).as_bytes()));
+    hash
+}
+
+/// A `DataIntegrityProof` using the `eddsa-jcs-2022` cryptosuite.
+///
+/// The document is canonicalized with JCS (RFC 8785) rather than RDF dataset
+/// canonicalization, so no JSON-LD processing is needed to sign or verify it.
+/// This is used instead of the older `Ed25519Signature2020` suite, which
+/// requires RDF canonicalization.
+///
+/// The cryptosuite expects `Multikey` verification methods. `did:key`
+/// documents provide those, but the methods of `did:onion` documents are
+/// `Ed25519VerificationKey2018`; their keys are accepted all the same.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DataIntegrityProof {
+    verification_method: String,
+    purpose: VerificationRelationship,
+    created: Option<String>,
+    challenge: Option<String>,
+    domain: Option<String>,
+}
+
+impl DataIntegrityProof {
+    /// Create a proof made with the key of `verification_method`, for `purpose`.
+    ///
+    /// The proof is timestamped with the current time, see `with_created()`.
+    pub fn new(verification_method: &str, purpose: VerificationRelationship) -> DataIntegrityProof {
+        DataIntegrityProof {
+            verification_method: verification_method.to_string(),
+            purpose,
+            created: Some(xsd_date_time(unix_timestamp())),
+            challenge: None,
+            domain: None,
+        }
+    }
+
+    /// Set the creation time, as an XML Schema `dateTime`.
+    pub fn with_created(mut self, created: &str) -> DataIntegrityProof {
+        self.created = Some(created.to_string());
+        self
+    }
+
+    /// Set the challenge given by a verifier, to prevent replays of a presentation.
+    pub fn with_challenge(mut self, challenge: &str) -> DataIntegrityProof {
+        self.challenge = Some(challenge.to_string());
+        self
+    }
+
+    /// Set the domain the proof is intended for.
+    pub fn with_domain(mut self, domain: &str) -> DataIntegrityProof {
+        self.domain = Some(domain.to_string());
+        self
+    }
+
+    /// The identifier of the verification method.
+    pub fn verification_method(&self) -> &str {
+        &self.verification_method
+    }
+
+    /// The purpose of the proof.
+    pub fn purpose(&self) -> VerificationRelationship {
+        self.purpose
+    }
+
+    /// The creation time, if any.
+    pub fn created(&self) -> Option<&str> {
+        self.created.as_deref()
+    }
+
+    /// The challenge, if any.
+    pub fn challenge(&self) -> Option<&str> {
+        self.challenge.as_deref()
+    }
+
+    /// The domain, if any.
+    pub fn domain(&self) -> Option<&str> {
+        self.domain.as_deref()
+    }
+
+    fn to_json(&self, document_contexts: Option<&Value>) -> Value {
+        let mut proof = Map::new();
+        if let Some(contexts) = document_contexts {
+            proof.insert("@context".to_string(), contexts.clone());
+        }
+        proof.insert("type".to_string(), json!(PROOF_TYPE));
+        proof.insert("cryptosuite".to_string(), json!(CRYPTOSUITE));
+        if let Some(created) = &self.created {
+            proof.insert("created".to_string(), json!(created));
+        }
+        proof.insert(
+            "verificationMethod".to_string(),
+            json!(self.verification_method),
+        );
+        proof.insert("proofPurpose".to_string(), json!(self.purpose.property()));
+        if let Some(challenge) = &self.challenge {
+            proof.insert("challenge".to_string(), json!(challenge));
+        }
+        if let Some(domain) = &self.domain {
+            proof.insert("domain".to_string(), json!(domain));
+        }
+        Value::Object(proof)
+    }
+
+    fn from_json(proof: &Map<String, Value>) -> Result<DataIntegrityProof> {
+        if proof_str(proof, "type")? != PROOF_TYPE
+            || proof_str(proof, "cryptosuite")? != CRYPTOSUITE
+        {
+            return Err(PError::InvalidProof(
+                "only eddsa-jcs-2022 proofs are supported",
+            ));
+        }
+        let optional = |name| match proof_str(proof, name) {
+            Ok(value) => Ok(Some(value.to_string())),
+            Err(PError::MissingField(_)) => Ok(None),
+            Err(err) => Err(err),
+        };
+        Ok(DataIntegrityProof {
+            verification_method: proof_str(proof, "verificationMethod")?.to_string(),
+            purpose: VerificationRelationship::from_property(proof_str(proof, "proofPurpose")?)
+                .ok_or(PError::InvalidProof("unknown proof purpose"))?,
+            created: optional("created")?,
+            challenge: optional("challenge")?,
+            domain: optional("domain")?,
+        })
+    }
+
+    /// Sign `document` with `sk`, and return it with this proof attached.
+    ///
+    /// The data integrity context is added to documents that don't already
+    /// include it through the credentials v2 context.
+    pub fn sign(&self, document: &Value, sk: &SecretKey) -> Result<Value> {
+        let mut document = document
+            .as_object()
+            .cloned()
+            .ok_or(PError::InvalidCredential("not a JSON object"))?;
+        if document.contains_key("proof") {
+            return Err(PError::InvalidProof("the document already has a proof"));
+        }
+        if let Some(context) = document.get_mut("@context") {
+            let known = contexts(Some(context));
+            if !known.contains(&CREDENTIALS_V2_CONTEXT) && !known.contains(&DATA_INTEGRITY_CONTEXT)
+            {
+                let mut extended = match context.take() {
+                    Value::Array(contexts) => contexts,
+                    context => vec![context],
+                };
+                extended.push(json!(DATA_INTEGRITY_CONTEXT));
+                *context = Value::Array(extended);
+            }
+        }
+        let mut document = Value::Object(document);
+        let mut proof = self.to_json(document.get("@context"));
+        let signature = sk.sign_raw(&hash_data(&proof, &document), None);
+        proof["proofValue"] = json!(format!("z{}", bs58::encode(&signature[..]).into_string()));
+        document["proof"] = proof;
+        Ok(document)
+    }
+
+    /// Verify the proof of `document` with the keys of `did_document`, and return it.
+    ///
+    /// The verification method must be listed in the DID document for the
+    /// purpose of the proof. The caller must check that the purpose, the
+    /// challenge and the domain are the expected ones.
+    pub fn verify(document: &Value, did_document: &DidDocument) -> Result<DataIntegrityProof> {
+        let mut document = document
+            .as_object()
+            .cloned()
+            .ok_or(PError::InvalidCredential("not a JSON object"))?;
+        let mut proof = match document.remove("proof") {
+            Some(Value::Object(proof)) => proof,
+            Some(_) => return Err(PError::InvalidProof("only a single proof is supported")),
+            None => return Err(PError::MissingField("proof")),
+        };
+        let proof_value = match proof.remove("proofValue") {
+            Some(Value::String(proof_value)) => proof_value,
+            _ => return Err(PError::MissingField("proofValue")),
+        };
+        let signature = proof_value
+            .strip_prefix('z')
+            .and_then(|b58| bs58::decode(b58).into_vec().ok())
+            .filter(|signature| signature.len() == 64)
+            .ok_or(PError::InvalidProof("invalid proof value"))?;
+        let parsed = DataIntegrityProof::from_json(&proof)?;
+        let document = Value::Object(document);
+        if let Some(proof_contexts) = proof.get("@context") {
+            if !contexts(document.get("@context")).starts_with(&contexts(Some(proof_contexts))) {
+                return Err(PError::InvalidProof(
+                    "the proof contexts must be a prefix of the document contexts",
+                ));
+            }
+        }
+
+        let (did, _) = parsed
+            .verification_method
+            .split_once('#')
+            .ok_or(PError::InvalidProof("invalid verification method"))?;
+        let method = did_document
+            .find_verification_method(&parsed.verification_method)
+            .filter(|_| did == did_document.id())
+            .ok_or(PError::InvalidProof(
+                "the verification method is not in the DID document",
+            ))?;
+        if !did_document
+            .verification_methods_for(parsed.purpose)
+            .any(|m| m == method)
+        {
+            return Err(PError::InvalidProof(
+                "the verification method cannot be used for the proof purpose",
+            ));
+        }
+        let pk = match method.key()? {
+            VerificationKey::Ed25519(pk) => pk,
+            VerificationKey::X25519(_) => {
+                return Err(PError::InvalidProof(
+                    "the verification method is not a signing key",
+                ))
+            }
+        };
+        if !ed25519::verify(
+            &hash_data(&Value::Object(proof), &document),
+            &pk.keynum_pk.pk,
+            &signature,
+        ) {
+            return Err(PError::ProofSignatureInvalid);
+        }
+        Ok(parsed)
+    }
+}
+
+/// The verification method of `did_document` for `purpose` whose key is the one of `sk`.
+fn signing_method(
+    did_document: &DidDocument,
+    sk: &SecretKey,
+    purpose: VerificationRelationship,
+) -> Result<String> {
+    let pk = sk.public_key();
+    did_document
+        .verification_methods_for(purpose)
+        .find(|method| match method.key() {
+            Ok(VerificationKey::Ed25519(key)) => key.keynum_pk.pk == pk.keynum_pk.pk,
+            _ => false,
+        })
+        .map(|method| did_document.verification_method_id(method))
+        .ok_or(PError::InvalidProof(
+            "the DID document has no verification method for this key",
+        ))
+}
+
+/// Issue a Verifiable Credential: sign `credential` with the assertion method of
+/// the issuer's DID document whose key is `sk`.
+///
+/// The `issuer` of the credential must be the DID of `did_document`.
+pub fn issue_credential(
+    credential: &Value,
+    sk: &SecretKey,
+    did_document: &DidDocument,
+) -> Result<Value> {
+    check_envelope(credential, "VerifiableCredential")?;
+    if issuer(credential)? != did_document.id() {
+        return Err(PError::InvalidCredential(
+            "the issuer is not the subject of the DID document",
+        ));
+    }
+    let method = signing_method(did_document, sk, VerificationRelationship::AssertionMethod)?;
+    DataIntegrityProof::new(&method, VerificationRelationship::AssertionMethod).sign(credential, sk)
+}
+
+/// Verify a Verifiable Credential issued by the subject of `did_document`.
+///
+/// Validity periods and the status of the credential are not checked.
+pub fn verify_credential(
+    credential: &Value,
+    did_document: &DidDocument,
+) -> Result<DataIntegrityProof> {
+    check_envelope(credential, "VerifiableCredential")?;
+    if issuer(credential)? != did_document.id() {
+        return Err(PError::InvalidCredential(
+            "the issuer is not the subject of the DID document",
+        ));
+    }
+    let proof = DataIntegrityProof::verify(credential, did_document)?;
+    if proof.purpose != VerificationRelationship::AssertionMethod {
+        return Err(PError::InvalidProof(
+            "the proof purpose must be assertionMethod",
+        ));
+    }
+    Ok(proof)
+}
+
+/// Sign a Verifiable Presentation with the authentication method of the
+/// holder's DID document whose key is `sk`.
+///
+/// `challenge` and `domain` are usually chosen by the verifier.
+pub fn sign_presentation(
+    presentation: &Value,
+    sk: &SecretKey,
+    did_document: &DidDocument,
+    challenge: Option<&str>,
+    domain: Option<&str>,
+) -> Result<Value> {
+    check_envelope(presentation, "VerifiablePresentation")?;
+    let method = signing_method(did_document, sk, VerificationRelationship::Authentication)?;
+    let mut proof = DataIntegrityProof::new(&method, VerificationRelationship::Authentication);
+    if let Some(challenge) = challenge {
+        proof = proof.with_challenge(challenge);
+    }
+    if let Some(domain) = domain {
+        proof = proof.with_domain(domain);
+    }
+    proof.sign(presentation, sk)
+}
+
+/// Verify a Verifiable Presentation signed by the subject of `did_document`.
+///
+/// The proof must carry exactly the given `challenge` and `domain`. The
+/// embedded credentials are not verified: each one must be checked with
+/// `verify_credential()` and the DID document of its issuer.
+pub fn verify_presentation(
+    presentation: &Value,
+    did_document: &DidDocument,
+    challenge: Option<&str>,
+    domain: Option<&str>,
+) -> Result<DataIntegrityProof> {
+    check_envelope(presentation, "VerifiablePresentation")?;
+    if let Some(holder) = presentation.get("holder") {
+        if holder.as_str() != Some(did_document.id()) {
+            return Err(PError::InvalidCredential(
+                "the holder is not the subject of the DID document",
+            ));
+        }
+    }
+    let proof = DataIntegrityProof::verify(presentation, did_document)?;
+    if proof.purpose != VerificationRelationship::Authentication {
+        return Err(PError::InvalidProof(
+            "the proof purpose must be authentication",
+        ));
+    }
+    if proof.challenge.as_deref() != challenge {
+        return Err(PError::InvalidProof("unexpected challenge"));
+    }
+    if proof.domain.as_deref() != domain {
+        return Err(PError::InvalidProof("unexpected domain"));
+    }
+    Ok(proof)
+}
@@ -279,7 +279,6 @@ impl Blake2b {
         copy_memory(&self.buf[0..outlen], out);
     }
 
-    #[allow(dead_code)]
     pub fn blake2b(out: &mut [u8], input: &[u8]) {
         let mut hasher: Blake2b = Blake2b::new(out.len());
         hasher.update(input);
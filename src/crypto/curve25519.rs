@@ -24,6 +24,7 @@ pub fn fiat_25519_addcarryx_u51(
 }
 
 #[inline]
+#[allow(clippy::unnecessary_cast)]
 pub fn fiat_25519_subborrowx_u51(
     out1: &mut u64,
     out2: &mut fiat_25519_u1,
@@ -39,6 +40,7 @@ pub fn fiat_25519_subborrowx_u51(
 }
 
 #[inline]
+#[allow(clippy::unnecessary_cast)]
 pub fn fiat_25519_cmovznz_u64(out1: &mut u64, arg1: fiat_25519_u1, arg2: u64, arg3: u64) {
     let x1: fiat_25519_u1 = (!(!arg1));
     let x2: u64 = ((((((0x0 as fiat_25519_i2) - (x1 as fiat_25519_i2)) as fiat_25519_i1) as i128)
@@ -48,6 +50,7 @@ pub fn fiat_25519_cmovznz_u64(out1: &mut u64, arg1: fiat_25519_u1, arg2: u64, ar
 }
 
 #[inline]
+#[allow(clippy::unnecessary_cast)]
 pub fn fiat_25519_carry_mul(out1: &mut [u64; 5], arg1: &[u64; 5], arg2: &[u64; 5]) {
     let x1: u128 = (((arg1[4]) as u128) * (((arg2[4]) * 0x13) as u128));
     let x2: u128 = (((arg1[4]) as u128) * (((arg2[3]) * 0x13) as u128));
@@ -109,6 +112,7 @@ pub fn fiat_25519_carry_mul(out1: &mut [u64; 5], arg1: &[u64; 5], arg2: &[u64; 5
 }
 
 #[inline]
+#[allow(clippy::unnecessary_cast)]
 pub fn fiat_25519_carry_square(out1: &mut [u64; 5], arg1: &[u64; 5]) {
     let x1: u64 = ((arg1[4]) * 0x13);
     let x2: u64 = (x1 * 0x2);
@@ -255,6 +259,7 @@ pub fn fiat_25519_selectznz(
 }
 
 #[inline]
+#[allow(clippy::unnecessary_cast)]
 pub fn fiat_25519_to_bytes(out1: &mut [u8; 32], arg1: &[u64; 5]) {
     let mut x1: u64 = 0;
     let mut x2: fiat_25519_u1 = 0;
@@ -511,6 +516,7 @@ impl Fe {
         h
     }
 
+    #[allow(clippy::wrong_self_convention)]
     pub fn to_bytes(&self) -> [u8; 32] {
         let &Fe(es) = &self.carry();
         let mut s_ = [0u8; 32];
@@ -532,6 +538,7 @@ impl Fe {
         self.0 = t
     }
 
+    #[allow(clippy::needless_borrow)]
     fn square(&self) -> Fe {
         let &Fe(f) = &self;
         let mut h = Fe::default();
@@ -579,6 +586,7 @@ impl Fe {
         (self.to_bytes()[0] & 1) != 0
     }
 
+    #[allow(clippy::needless_borrow)]
     fn neg(&self) -> Fe {
         let &Fe(f) = &self;
         let mut h = Fe::default();
@@ -653,6 +661,7 @@ pub struct GeCached {
 }
 
 impl GeP1P1 {
+    #[allow(clippy::wrong_self_convention)]
     fn to_p2(&self) -> GeP2 {
         GeP2 {
             x: self.x * self.t,
@@ -661,6 +670,7 @@ impl GeP1P1 {
         }
     }
 
+    #[allow(clippy::wrong_self_convention)]
     fn to_p3(&self) -> GeP3 {
         GeP3 {
             x: self.x * self.t,
@@ -680,6 +690,7 @@ impl GeP2 {
         }
     }
 
+    #[allow(clippy::wrong_self_convention)]
     pub fn to_bytes(&self) -> [u8; 32] {
         let recip = self.z.invert();
         let x = self.x * recip;
@@ -830,6 +841,7 @@ impl GeP3 {
         Some(GeP3 { x, y, z, t })
     }
 
+    #[allow(clippy::wrong_self_convention)]
     fn to_p2(&self) -> GeP2 {
         GeP2 {
             x: self.x,
@@ -838,6 +850,7 @@ impl GeP3 {
         }
     }
 
+    #[allow(clippy::wrong_self_convention)]
     fn to_cached(&self) -> GeCached {
         GeCached {
             y_plus_x: self.y + self.x,
@@ -860,6 +873,7 @@ impl GeP3 {
         self.to_p2().dbl()
     }
 
+    #[allow(clippy::wrong_self_convention)]
     pub fn to_bytes(&self) -> [u8; 32] {
         let recip = self.z.invert();
         let x = self.x * recip;
@@ -975,6 +989,7 @@ impl Sub<GePrecomp> for GeP3 {
     }
 }
 
+#[allow(clippy::unnecessary_cast)]
 pub fn ge_scalarmult_base(scalar: &[u8]) -> GeP3 {
     const BXP: [u8; 32] = [
         0x1a, 0xd5, 0x25, 0x8f, 0x60, 0x2d, 0x56, 0xc9, 0xb2, 0xa7, 0x25, 0x95, 0x60, 0xc7, 0x2c,
@@ -1005,6 +1020,26 @@ pub fn ge_scalarmult_base(scalar: &[u8]) -> GeP3 {
     p
 }
 
+/// Map an Edwards point to the u coordinate of the birationally equivalent
+/// Montgomery point: `u = (1 + y) / (1 - y)`.
+pub fn ge_to_montgomery(point: &[u8]) -> Option<[u8; 32]> {
+    GeP3::from_bytes_negate_vartime(point)?;
+    if is_identity(point) {
+        return None;
+    }
+    let y = Fe::from_bytes(point);
+    Some(((FE_ONE + y) * (FE_ONE - y).invert()).to_bytes())
+}
+
+/// Multiply an arbitrary point by a scalar, which must be reduced mod l.
+pub fn ge_scalarmult_vartime(scalar: &[u8], point: &[u8]) -> Option<[u8; 32]> {
+    let minus_a = GeP3::from_bytes_negate_vartime(point)?;
+    let mut r = GeP2::double_scalarmult_vartime(scalar, minus_a, &[0u8; 32]).to_bytes();
+    r[31] ^= 0x80;
+    Some(r)
+}
+
+#[allow(clippy::needless_late_init)]
 pub fn sc_reduce(s: &mut [u8]) {
     let mut s0: i64 = 2097151 & load_3i(s);
     let mut s1: i64 = 2097151 & (load_4i(&s[2..6]) >> 5);
@@ -1326,6 +1361,7 @@ pub fn sc_reduce(s: &mut [u8]) {
     s[31] = (s11 >> 17) as u8;
 }
 
+#[allow(clippy::needless_late_init)]
 pub fn sc_muladd(s: &mut [u8], a: &[u8], b: &[u8], c: &[u8]) {
     let a0 = 2097151 & load_3i(&a[0..3]);
     let a1 = 2097151 & (load_4i(&a[2..6]) >> 5);
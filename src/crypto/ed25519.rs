@@ -1,5 +1,11 @@
-use super::curve25519::{ge_scalarmult_base, is_identity, sc_muladd, sc_reduce, GeP2, GeP3};
+use super::curve25519::{
+    ge_scalarmult_base, ge_scalarmult_vartime, ge_to_montgomery, is_identity, sc_muladd, sc_reduce,
+    GeP2, GeP3,
+};
 use super::sha512;
+use std::convert::Infallible;
+use std::io::{self, Read, Seek, SeekFrom};
+use zeroize::Zeroizing;
 
 static L: [u8; 32] = [
     0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
@@ -23,34 +29,99 @@ fn check_s_lt_l(s: &[u8]) -> bool {
     c == 0
 }
 
+fn absorb_reader<R: Read>(hasher: &mut sha512::Hash, data_reader: &mut R) -> io::Result<()> {
+    let mut buf = vec![0u8; 65536];
+    loop {
+        let len = data_reader.read(&mut buf)?;
+        if len == 0 {
+            break;
+        }
+        hasher.update(&buf[..len]);
+    }
+    Ok(())
+}
+
 pub fn verify(message: &[u8], public_key: &[u8], signature: &[u8]) -> bool {
-    if check_s_lt_l(&signature[32..64]) || is_identity(public_key) {
-        return false;
+    let res: Result<bool, Infallible> = verify_with(public_key, signature, |hasher| {
+        hasher.update(message);
+        Ok(())
+    });
+    match res {
+        Ok(valid) => valid,
+        Err(e) => match e {},
     }
-    let a = match GeP3::from_bytes_negate_vartime(public_key) {
-        Some(g) => g,
-        None => {
-            return false;
+}
+
+/// Verify a signature over the content of `data_reader`, read in a single pass.
+pub fn verify_reader<R: Read>(
+    data_reader: &mut R,
+    public_key: &[u8],
+    signature: &[u8],
+) -> io::Result<bool> {
+    verify_with(public_key, signature, |hasher| {
+        absorb_reader(hasher, data_reader)
+    })
+}
+
+fn verify_with<F, E>(public_key: &[u8], signature: &[u8], mut absorb_message: F) -> Result<bool, E>
+where
+    F: FnMut(&mut sha512::Hash) -> Result<(), E>,
+{
+    let mut state = VerifyState::new(public_key, signature);
+    absorb_message(&mut state.hasher)?;
+    Ok(state.finalize())
+}
+
+/// Incremental verification state, for messages that arrive in chunks.
+#[derive(Clone)]
+pub struct VerifyState {
+    hasher: sha512::Hash,
+    a: Option<GeP3>,
+    signature: [u8; 64],
+}
+
+impl VerifyState {
+    pub fn new(public_key: &[u8], signature: &[u8]) -> VerifyState {
+        let mut sig = [0u8; 64];
+        sig.copy_from_slice(&signature[0..64]);
+        let mut hasher = sha512::Hash::new();
+        hasher.update(&signature[0..32]);
+        hasher.update(public_key);
+        let a = if check_s_lt_l(&signature[32..64])
+            || is_identity(public_key)
+            || public_key.iter().fold(0, |acc, x| acc | x) == 0
+        {
+            None
+        } else {
+            GeP3::from_bytes_negate_vartime(public_key)
+        };
+        VerifyState {
+            hasher,
+            a,
+            signature: sig,
         }
-    };
-    if public_key.iter().fold(0, |acc, x| acc | x) == 0 {
-        return false;
     }
 
-    let mut hasher = sha512::Hash::new();
-    hasher.update(&signature[0..32]);
-    hasher.update(public_key);
-    hasher.update(message);
-    let mut hash = hasher.finalize();
-    sc_reduce(&mut hash);
+    pub fn update(&mut self, message: &[u8]) {
+        self.hasher.update(message);
+    }
 
-    let r = GeP2::double_scalarmult_vartime(hash.as_ref(), a, &signature[32..64]);
-    r.to_bytes()
-        .as_ref()
-        .iter()
-        .zip(signature.iter())
-        .fold(0, |acc, (x, y)| acc | (x ^ y))
-        == 0
+    pub fn finalize(self) -> bool {
+        let a = match self.a {
+            Some(a) => a,
+            None => return false,
+        };
+        let mut hash = self.hasher.finalize();
+        sc_reduce(&mut hash);
+
+        let r = GeP2::double_scalarmult_vartime(hash.as_ref(), a, &self.signature[32..64]);
+        r.to_bytes()
+            .as_ref()
+            .iter()
+            .zip(self.signature.iter())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+    }
 }
 
 pub fn keypair(seed: &[u8]) -> ([u8; 64], [u8; 32], [u8; 64]) {
@@ -65,12 +136,12 @@ pub fn keypair(seed: &[u8]) -> ([u8; 64], [u8; 32], [u8; 64]) {
 
     let a = ge_scalarmult_base(&secret[0..32]);
     let public_key = a.to_bytes();
-    for (dest, src) in (&mut secret[32..64]).iter_mut().zip(public_key.iter()) {
+    for (dest, src) in secret[32..64].iter_mut().zip(public_key.iter()) {
         *dest = *src;
     }
 
     // puts back the original seed in!!
-    for (dest, src) in (&mut secret[0..32]).iter_mut().zip(seed.iter()) {
+    for (dest, src) in secret[0..32].iter_mut().zip(seed.iter()) {
         *dest = *src;
     }
 
@@ -78,40 +149,171 @@ pub fn keypair(seed: &[u8]) -> ([u8; 64], [u8; 32], [u8; 64]) {
 }
 
 pub fn signature(message: &[u8], secret_key: &[u8], z: Option<&[u8]>) -> [u8; 64] {
+    let res: Result<[u8; 64], Infallible> = signature_with(secret_key, z, |hasher| {
+        hasher.update(message);
+        Ok(())
+    });
+    match res {
+        Ok(signature) => signature,
+        Err(e) => match e {},
+    }
+}
+
+/// Sign the content of `data_reader` without buffering it.
+///
+/// The message is hashed twice (nonce, then challenge), so the reader is
+/// rewound to its initial position between both passes.
+pub fn signature_reader<R: Read + Seek>(
+    data_reader: &mut R,
+    secret_key: &[u8],
+    z: Option<&[u8]>,
+) -> io::Result<[u8; 64]> {
+    let start = data_reader.stream_position()?;
+    signature_with(secret_key, z, |hasher| {
+        data_reader.seek(SeekFrom::Start(start))?;
+        absorb_reader(hasher, data_reader)
+    })
+}
+
+/// Sign `message` with an expanded secret key (clamped scalar and nonce prefix),
+/// as stored by Tor, for which the seed is not known.
+pub fn signature_expanded(
+    message: &[u8],
+    expanded_secret_key: &[u8],
+    public_key: &[u8],
+    z: Option<&[u8]>,
+) -> [u8; 64] {
+    let res: Result<[u8; 64], Infallible> =
+        signature_with_expanded(expanded_secret_key, public_key, z, |hasher| {
+            hasher.update(message);
+            Ok(())
+        });
+    match res {
+        Ok(signature) => signature,
+        Err(e) => match e {},
+    }
+}
+
+/// Sign the content of `data_reader` with an expanded secret key.
+pub fn signature_expanded_reader<R: Read + Seek>(
+    data_reader: &mut R,
+    expanded_secret_key: &[u8],
+    public_key: &[u8],
+    z: Option<&[u8]>,
+) -> io::Result<[u8; 64]> {
+    let start = data_reader.stream_position()?;
+    signature_with_expanded(expanded_secret_key, public_key, z, |hasher| {
+        data_reader.seek(SeekFrom::Start(start))?;
+        absorb_reader(hasher, data_reader)
+    })
+}
+
+/// Compute the public key of an expanded secret key.
+pub fn expanded_public_key(expanded_secret_key: &[u8]) -> [u8; 32] {
+    ge_scalarmult_base(&expanded_secret_key[0..32]).to_bytes()
+}
+
+/// Returns `true` if `public_key` encodes a point of the curve other than the identity.
+pub fn is_valid_public_key(public_key: &[u8]) -> bool {
+    public_key.len() == 32
+        && GeP3::from_bytes_negate_vartime(public_key).is_some()
+        && !is_identity(public_key)
+}
+
+/// Convert an Ed25519 public key to the X25519 public key of the same secret scalar.
+pub fn x25519_public_key(public_key: &[u8]) -> Option<[u8; 32]> {
+    ge_to_montgomery(public_key)
+}
+
+fn blinding_scalar(param: &[u8]) -> [u8; 64] {
+    let mut h = [0u8; 64];
+    h[0..32].copy_from_slice(&param[0..32]);
+    h[0] &= 248;
+    h[31] &= 63;
+    h[31] |= 64;
+    sc_reduce(&mut h);
+    h
+}
+
+/// Blind a public key with a 32-byte parameter: `A' = h A`, `h` being the clamped parameter.
+pub fn blind_public_key(public_key: &[u8], param: &[u8]) -> Option<[u8; 32]> {
+    ge_scalarmult_vartime(&blinding_scalar(param)[0..32], public_key)
+}
+
+/// Blind an expanded secret key with the same parameter as `blind_public_key`.
+///
+/// The scalar becomes `a' = h a mod l`, and the nonce prefix is rehashed.
+pub fn blind_expanded_secret_key(expanded_secret_key: &[u8], param: &[u8]) -> [u8; 64] {
+    let h = blinding_scalar(param);
+    let mut blinded = [0u8; 64];
+    sc_muladd(
+        &mut blinded[0..32],
+        &h[0..32],
+        &expanded_secret_key[0..32],
+        &[0u8; 32],
+    );
+    let mut hasher = sha512::Hash::new();
+    hasher.update(b"Derive temporary signing key hash input");
+    hasher.update(&expanded_secret_key[32..64]);
+    let prefix = Zeroizing::new(hasher.finalize());
+    blinded[32..64].copy_from_slice(&prefix[0..32]);
+    blinded
+}
+
+fn signature_with<F, E>(
+    secret_key: &[u8],
+    z: Option<&[u8]>,
+    absorb_message: F,
+) -> Result<[u8; 64], E>
+where
+    F: FnMut(&mut sha512::Hash) -> Result<(), E>,
+{
     let seed = &secret_key[0..32];
     let public_key = &secret_key[32..64];
-    let az: [u8; 64] = {
+    let az = Zeroizing::new({
         let mut hash_output = sha512::Hash::hash(seed);
         hash_output[0] &= 248;
         hash_output[31] &= 63;
         hash_output[31] |= 64;
         hash_output
-    };
-    let nonce = {
+    });
+    signature_with_expanded(&az[..], public_key, z, absorb_message)
+}
+
+fn signature_with_expanded<F, E>(
+    az: &[u8],
+    public_key: &[u8],
+    z: Option<&[u8]>,
+    mut absorb_message: F,
+) -> Result<[u8; 64], E>
+where
+    F: FnMut(&mut sha512::Hash) -> Result<(), E>,
+{
+    let nonce = Zeroizing::new({
         let mut hasher = sha512::Hash::new();
         if let Some(z) = z {
             hasher.update(z);
-            hasher.update(&az[..]);
+            hasher.update(&az[0..64]);
         } else {
             hasher.update(&az[32..64]);
         }
-        hasher.update(message);
+        absorb_message(&mut hasher)?;
         let mut hash_output = hasher.finalize();
         sc_reduce(&mut hash_output[0..64]);
         hash_output
-    };
+    });
     let mut signature: [u8; 64] = [0; 64];
     let r: GeP3 = ge_scalarmult_base(&nonce[0..32]);
-    for (result_byte, source_byte) in (&mut signature[0..32]).iter_mut().zip(r.to_bytes().iter()) {
+    for (result_byte, source_byte) in signature[0..32].iter_mut().zip(r.to_bytes().iter()) {
         *result_byte = *source_byte;
     }
-    for (result_byte, source_byte) in (&mut signature[32..64]).iter_mut().zip(public_key.iter()) {
+    for (result_byte, source_byte) in signature[32..64].iter_mut().zip(public_key.iter()) {
         *result_byte = *source_byte;
     }
     {
         let mut hasher = sha512::Hash::new();
         hasher.update(signature.as_ref());
-        hasher.update(message);
+        absorb_message(&mut hasher)?;
         let mut hram = hasher.finalize();
         sc_reduce(&mut hram);
         sc_muladd(
@@ -121,5 +323,5 @@ pub fn signature(message: &[u8], secret_key: &[u8], z: Option<&[u8]>) -> [u8; 64
             &nonce[0..32],
         );
     }
-    signature
+    Ok(signature)
 }
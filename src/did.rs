@@ -0,0 +1,779 @@
+use crate::constants::*;
+use crate::crypto::ed25519;
+use crate::errors::*;
+use crate::keynum::*;
+use crate::multibase::*;
+use crate::onion_address::*;
+use crate::public_key::*;
+use crate::secret_key::*;
+use data_encoding::base64url;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+const DID_CONTEXT: &str = "https://www.w3.org/ns/did/v1";
+const DID_ONION_PREFIX: &str = "did:onion:";
+
+fn parse_onion_did(did: &str) -> Result<OnionAddress> {
+    let addr = did
+        .strip_prefix(DID_ONION_PREFIX)
+        .ok_or(PError::InvalidDid("not a did:onion identifier"))?;
+    let onion_address = addr.parse::<OnionAddress>()?;
+    // the method-specific identifier must be in canonical form
+    if onion_address.without_suffix() != addr {
+        return Err(PError::InvalidDid(
+            "did:onion identifiers are 56 lowercase characters",
+        ));
+    }
+    Ok(onion_address)
+}
+
+fn key_bytes(bytes: &[u8]) -> Result<[u8; 32]> {
+    let mut key = [0u8; 32];
+    if bytes.len() != key.len() {
+        return Err(PError::InvalidDidDocument(
+            "public keys must be 32 bytes long",
+        ));
+    }
+    key.copy_from_slice(bytes);
+    Ok(key)
+}
+
+/// The public key of a verification method.
+#[derive(Clone, Debug, PartialEq)]
+pub enum VerificationKey {
+    /// A signing key; the key identifier of the `PublicKey` is all zeros.
+    Ed25519(PublicKey),
+    /// A key agreement key.
+    X25519([u8; 32]),
+}
+
+impl VerificationKey {
+    fn ed25519(bytes: &[u8]) -> Result<VerificationKey> {
+        let pk = key_bytes(bytes)?;
+        if !ed25519::is_valid_public_key(&pk) {
+            return Err(PError::InvalidDidDocument("invalid Ed25519 public key"));
+        }
+        Ok(VerificationKey::Ed25519(PublicKey {
+            sig_alg: SIGALG,
+            keynum_pk: KeynumPK {
+                keynum: [0u8; KEYNUM_BYTES],
+                pk,
+            },
+        }))
+    }
+}
+
+/// A verification relationship between the DID subject and a verification method.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum VerificationRelationship {
+    Authentication,
+    AssertionMethod,
+    KeyAgreement,
+    CapabilityInvocation,
+    CapabilityDelegation,
+}
+
+impl VerificationRelationship {
+    const ALL: [VerificationRelationship; 5] = [
+        VerificationRelationship::Authentication,
+        VerificationRelationship::AssertionMethod,
+        VerificationRelationship::KeyAgreement,
+        VerificationRelationship::CapabilityInvocation,
+        VerificationRelationship::CapabilityDelegation,
+    ];
+
+    /// The name of the property in a DID document.
+    pub fn property(self) -> &'static str {
+        match self {
+            VerificationRelationship::Authentication => "authentication",
+            VerificationRelationship::AssertionMethod => "assertionMethod",
+            VerificationRelationship::KeyAgreement => "keyAgreement",
+            VerificationRelationship::CapabilityInvocation => "capabilityInvocation",
+            VerificationRelationship::CapabilityDelegation => "capabilityDelegation",
+        }
+    }
+
+    /// The relationship named `property` in a DID document.
+    pub(crate) fn from_property(property: &str) -> Option<VerificationRelationship> {
+        VerificationRelationship::ALL
+            .iter()
+            .copied()
+            .find(|relationship| relationship.property() == property)
+    }
+}
+
+/// A verification method, i.e. a public key, of a DID document.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VerificationMethod {
+    fragment: String,
+    method_type: String,
+    controller: Option<String>,
+    property: String,
+    material: Value,
+}
+
+impl VerificationMethod {
+    /// Create a verification method of type `method_type`, whose key is the
+    /// value of `property`, e.g. `publicKeyJwk` or `publicKeyMultibase`.
+    ///
+    /// The fragment of its identifier is the base64url-encoded SHA-256 hash
+    /// of the compact JSON serialization of the key, so that it doesn't change
+    /// from one document to the next.
+    pub fn new(method_type: &str, property: &str, material: Value) -> VerificationMethod {
+        let digest = Sha256::digest(material.to_string().as_bytes());
+        VerificationMethod {
+            fragment: base64url::encode_nopad(&digest),
+            method_type: method_type.to_string(),
+            controller: None,
+            property: property.to_string(),
+            material,
+        }
+    }
+
+    /// An Ed25519 key, as a `JsonWebKey2020`.
+    pub fn ed25519_jwk(pk: &PublicKey) -> VerificationMethod {
+        VerificationMethod::okp_jwk("Ed25519", &pk.keynum_pk.pk)
+    }
+
+    /// An X25519 key agreement key, as a `JsonWebKey2020`.
+    pub fn x25519_jwk(x25519_pk: &[u8; 32]) -> VerificationMethod {
+        VerificationMethod::okp_jwk("X25519", x25519_pk)
+    }
+
+    fn okp_jwk(crv: &str, x: &[u8]) -> VerificationMethod {
+        // lexicographically ordered for the purpose of digesting this object into the id
+        let jwk = json!({"crv": crv, "kty": "OKP", "x": base64url::encode_nopad(x)});
+        VerificationMethod::new("JsonWebKey2020", "publicKeyJwk", jwk)
+    }
+
+    /// An Ed25519 key, as an `Ed25519VerificationKey2018`.
+    pub fn ed25519_base58(pk: &PublicKey) -> VerificationMethod {
+        let base58 = bs58::encode(&pk.keynum_pk.pk).into_string();
+        VerificationMethod::new(
+            "Ed25519VerificationKey2018",
+            "publicKeyBase58",
+            Value::String(base58),
+        )
+    }
+
+    /// An Ed25519 key, as an `Ed25519VerificationKey2020`.
+    ///
+    /// As in `did:key` documents, the fragment is the multibase-encoded key.
+    pub fn ed25519_multibase(pk: &PublicKey) -> VerificationMethod {
+        let multibase = multibase_encode(MULTICODEC_ED25519_PUB, &pk.keynum_pk.pk);
+        VerificationMethod::new(
+            "Ed25519VerificationKey2020",
+            "publicKeyMultibase",
+            Value::String(multibase.clone()),
+        )
+        .with_fragment(&multibase)
+    }
+
+    /// An X25519 key agreement key, as an `X25519KeyAgreementKey2020`.
+    ///
+    /// As in `did:key` documents, the fragment is the multibase-encoded key.
+    pub fn x25519_multibase(x25519_pk: &[u8; 32]) -> VerificationMethod {
+        let multibase = multibase_encode(MULTICODEC_X25519_PUB, x25519_pk);
+        VerificationMethod::new(
+            "X25519KeyAgreementKey2020",
+            "publicKeyMultibase",
+            Value::String(multibase.clone()),
+        )
+        .with_fragment(&multibase)
+    }
+
+    /// Replace the fragment of the identifier of this method.
+    pub fn with_fragment(mut self, fragment: &str) -> VerificationMethod {
+        self.fragment = fragment.trim_start_matches('#').to_string();
+        self
+    }
+
+    /// Set the controller of this method; it defaults to the DID subject.
+    pub fn with_controller(mut self, controller: &str) -> VerificationMethod {
+        self.controller = Some(controller.to_string());
+        self
+    }
+
+    /// The fragment of the identifier of this method, without the `#`.
+    pub fn fragment(&self) -> &str {
+        &self.fragment
+    }
+
+    /// The type of this method, e.g. `JsonWebKey2020`.
+    pub fn method_type(&self) -> &str {
+        &self.method_type
+    }
+
+    /// The controller of this method, if it's not the DID subject.
+    pub fn controller(&self) -> Option<&str> {
+        self.controller.as_deref()
+    }
+
+    /// The key, as found in the document.
+    pub fn material(&self) -> &Value {
+        &self.material
+    }
+
+    /// Decode the key of this method.
+    ///
+    /// Keys can be OKP JWKs, base58 or multibase encoded.
+    pub fn key(&self) -> Result<VerificationKey> {
+        let unsupported = PError::InvalidDidDocument("unsupported verification method");
+        match (self.property.as_str(), &self.material) {
+            ("publicKeyJwk", Value::Object(jwk)) => {
+                if jwk.get("kty") != Some(&json!("OKP")) {
+                    return Err(unsupported);
+                }
+                let x = jwk
+                    .get("x")
+                    .and_then(Value::as_str)
+                    .ok_or(PError::MissingField("x"))?;
+                let x = base64url::decode_nopad(x.as_bytes())
+                    .map_err(|_| PError::InvalidDidDocument("invalid JWK encoding"))?;
+                match jwk.get("crv").and_then(Value::as_str) {
+                    Some("Ed25519") => VerificationKey::ed25519(&x),
+                    Some("X25519") => Ok(VerificationKey::X25519(key_bytes(&x)?)),
+                    _ => Err(unsupported),
+                }
+            }
+            ("publicKeyBase58", Value::String(b58)) => {
+                let key = bs58::decode(b58)
+                    .into_vec()
+                    .map_err(|_| PError::InvalidDidDocument("invalid base58 encoding"))?;
+                match self.method_type.as_str() {
+                    "Ed25519VerificationKey2018" | "Ed25519VerificationKey2020" => {
+                        VerificationKey::ed25519(&key)
+                    }
+                    "X25519KeyAgreementKey2019" => Ok(VerificationKey::X25519(key_bytes(&key)?)),
+                    _ => Err(unsupported),
+                }
+            }
+            ("publicKeyMultibase", Value::String(multibase)) => {
+                let (codec, key) = multibase_decode(multibase)
+                    .ok_or(PError::InvalidDidDocument("invalid multibase encoding"))?;
+                match codec {
+                    MULTICODEC_ED25519_PUB => VerificationKey::ed25519(&key),
+                    MULTICODEC_X25519_PUB => Ok(VerificationKey::X25519(key_bytes(&key)?)),
+                    _ => Err(unsupported),
+                }
+            }
+            _ => Err(unsupported),
+        }
+    }
+
+    fn from_json(method: &Value, did: &str) -> Result<VerificationMethod> {
+        let method = method.as_object().ok_or(PError::InvalidDidDocument(
+            "verification methods must be objects",
+        ))?;
+        let field = |name: &'static str| {
+            method
+                .get(name)
+                .and_then(Value::as_str)
+                .ok_or(PError::MissingField(name))
+        };
+        let fragment = parse_fragment(field("id")?, did)?;
+        let method_type = field("type")?;
+        let controller = field("controller")?;
+        let (property, material) = ["publicKeyJwk", "publicKeyBase58", "publicKeyMultibase"]
+            .iter()
+            .find_map(|property| method.get(*property).map(|value| (*property, value)))
+            .ok_or(PError::MissingField("publicKeyJwk"))?;
+        Ok(VerificationMethod {
+            fragment: fragment.to_string(),
+            method_type: method_type.to_string(),
+            controller: Some(controller.to_string()).filter(|controller| controller != did),
+            property: property.to_string(),
+            material: material.clone(),
+        })
+    }
+
+    fn context(&self) -> Option<&'static str> {
+        match self.method_type.as_str() {
+            "JsonWebKey2020" => Some("https://w3id.org/security/suites/jws-2020/v1"),
+            "Ed25519VerificationKey2018" => {
+                Some("https://w3id.org/security/suites/ed25519-2018/v1")
+            }
+            "Ed25519VerificationKey2020" => {
+                Some("https://w3id.org/security/suites/ed25519-2020/v1")
+            }
+            "X25519KeyAgreementKey2020" => Some("https://w3id.org/security/suites/x25519-2020/v1"),
+            "Multikey" => Some("https://w3id.org/security/multikey/v1"),
+            _ => None,
+        }
+    }
+}
+
+/// A service endpoint of a DID document.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DidService {
+    fragment: String,
+    service_type: String,
+    endpoint: String,
+}
+
+impl DidService {
+    /// Create a service, e.g. `DidService::new("website", "LinkedDomains", "http://example.onion/")`.
+    pub fn new(fragment: &str, service_type: &str, endpoint: &str) -> DidService {
+        DidService {
+            fragment: fragment.trim_start_matches('#').to_string(),
+            service_type: service_type.to_string(),
+            endpoint: endpoint.to_string(),
+        }
+    }
+
+    /// The fragment of the identifier of this service, without the `#`.
+    pub fn fragment(&self) -> &str {
+        &self.fragment
+    }
+
+    /// The type of this service.
+    pub fn service_type(&self) -> &str {
+        &self.service_type
+    }
+
+    /// The URI of this service.
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    fn from_json(service: &Value, did: &str) -> Result<DidService> {
+        let field = |name: &'static str| {
+            service
+                .get(name)
+                .and_then(Value::as_str)
+                .ok_or(PError::MissingField(name))
+        };
+        Ok(DidService::new(
+            parse_fragment(field("id")?, did)?,
+            field("type")?,
+            field("serviceEndpoint")?,
+        ))
+    }
+}
+
+/// The fragment of a DID URL that must be either relative or within `did`.
+fn parse_fragment<'a>(id: &'a str, did: &str) -> Result<&'a str> {
+    match id.split_once('#') {
+        Some((base, fragment)) if (base.is_empty() || base == did) && !fragment.is_empty() => {
+            Ok(fragment)
+        }
+        _ => Err(PError::InvalidDidDocument(
+            "identifiers must be fragments of the DID",
+        )),
+    }
+}
+
+/// A string or a set of strings.
+fn string_set<'a>(value: &'a Value, reason: &'static str) -> Result<Vec<&'a str>> {
+    match value {
+        Value::String(s) => Ok(vec![s]),
+        Value::Array(values) => values
+            .iter()
+            .map(|value| value.as_str().ok_or(PError::InvalidDidDocument(reason)))
+            .collect(),
+        _ => Err(PError::InvalidDidDocument(reason)),
+    }
+}
+
+/// A DID document (DID Core, section 5), serialized as JSON-LD.
+///
+/// Verification method and service identifiers are fragments, made absolute
+/// with the DID when the document is serialized.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DidDocument {
+    id: String,
+    contexts: Vec<String>,
+    controllers: Vec<String>,
+    also_known_as: Vec<String>,
+    verification_methods: Vec<(VerificationMethod, Vec<VerificationRelationship>)>,
+    services: Vec<DidService>,
+}
+
+impl DidDocument {
+    /// Create an empty document for the DID `id`.
+    pub fn new(id: &str) -> DidDocument {
+        DidDocument {
+            id: id.to_string(),
+            contexts: vec![],
+            controllers: vec![],
+            also_known_as: vec![],
+            verification_methods: vec![],
+            services: vec![],
+        }
+    }
+
+    /// The `did:onion` document of an onion service identity key.
+    ///
+    /// The key agreement key is the X25519 form of the identity key.
+    pub fn onion(pk: &PublicKey) -> Result<DidDocument> {
+        Ok(DidDocument::onion_with_x25519(pk, &pk.to_x25519()?))
+    }
+
+    /// The `did:onion` document of an onion service, choosing how its key
+    /// agreement key is derived.
+    ///
+    /// `X25519Derivation::Legacy` reproduces the keys of earlier versions.
+    pub fn onion_from_secret_key(
+        secret: &SecretKey,
+        derivation: X25519Derivation,
+    ) -> Result<DidDocument> {
+        let x25519_sk = x25519_dalek::StaticSecret::from(*secret.to_x25519(derivation)?);
+        let x25519_pk = x25519_dalek::PublicKey::from(&x25519_sk);
+        Ok(DidDocument::onion_with_x25519(
+            &secret.public_key(),
+            x25519_pk.as_bytes(),
+        ))
+    }
+
+    fn onion_with_x25519(pk: &PublicKey, x25519_pk: &[u8; 32]) -> DidDocument {
+        use VerificationRelationship::*;
+
+        let did = format!("did:onion:{}", pk.to_onion_address().without_suffix());
+        DidDocument::new(&did)
+            .verification_method(
+                VerificationMethod::ed25519_jwk(pk),
+                &[Authentication, CapabilityInvocation, CapabilityDelegation],
+            )
+            .verification_method(VerificationMethod::x25519_jwk(x25519_pk), &[KeyAgreement])
+            // use ed25519 signature 2018 for assertion, use jws elsewhere
+            .verification_method(VerificationMethod::ed25519_base58(pk), &[AssertionMethod])
+    }
+
+    /// The `did:key` document of an Ed25519 key.
+    ///
+    /// The key agreement key is the X25519 form of the key, as specified by
+    /// the `did:key` method.
+    pub fn did_key(pk: &PublicKey) -> Result<DidDocument> {
+        use VerificationRelationship::*;
+
+        Ok(DidDocument::new(&pk.to_did_key())
+            .verification_method(
+                VerificationMethod::ed25519_multibase(pk),
+                &[
+                    Authentication,
+                    AssertionMethod,
+                    CapabilityInvocation,
+                    CapabilityDelegation,
+                ],
+            )
+            .verification_method(
+                VerificationMethod::x25519_multibase(&pk.to_x25519()?),
+                &[KeyAgreement],
+            ))
+    }
+
+    /// The DID of the subject of this document.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Add a JSON-LD context, for verification method or service types that need one.
+    pub fn context(mut self, context: &str) -> DidDocument {
+        self.contexts.push(context.to_string());
+        self
+    }
+
+    /// Add a controller of the DID; by default, the subject controls itself.
+    pub fn controller(mut self, controller: &str) -> DidDocument {
+        self.controllers.push(controller.to_string());
+        self
+    }
+
+    /// Add an `alsoKnownAs` URI, e.g. another DID of the same subject.
+    pub fn also_known_as(mut self, uri: &str) -> DidDocument {
+        self.also_known_as.push(uri.to_string());
+        self
+    }
+
+    /// Add a verification method, usable for each of `relationships`.
+    pub fn verification_method(
+        mut self,
+        method: VerificationMethod,
+        relationships: &[VerificationRelationship],
+    ) -> DidDocument {
+        self.verification_methods
+            .push((method, relationships.to_vec()));
+        self
+    }
+
+    /// Add a service endpoint.
+    pub fn service(mut self, service: DidService) -> DidDocument {
+        self.services.push(service);
+        self
+    }
+
+    /// Add the HTTP endpoint of a `did:onion` subject, as a `LinkedDomains` service.
+    pub fn onion_http_service(self) -> Result<DidDocument> {
+        let onion_address = parse_onion_did(&self.id)?;
+        let endpoint = format!("http://{}/", onion_address);
+        Ok(self.service(DidService::new("onion", "LinkedDomains", &endpoint)))
+    }
+
+    /// The verification methods of this document.
+    pub fn verification_methods(&self) -> impl Iterator<Item = &VerificationMethod> {
+        self.verification_methods.iter().map(|(method, _)| method)
+    }
+
+    /// The verification methods usable for `relationship`.
+    pub fn verification_methods_for(
+        &self,
+        relationship: VerificationRelationship,
+    ) -> impl Iterator<Item = &VerificationMethod> {
+        self.verification_methods
+            .iter()
+            .filter(move |(_, relationships)| relationships.contains(&relationship))
+            .map(|(method, _)| method)
+    }
+
+    /// The verification method with the identifier `id`, absolute or relative.
+    pub fn find_verification_method(&self, id: &str) -> Option<&VerificationMethod> {
+        let fragment = parse_fragment(id, &self.id).ok()?;
+        self.verification_methods()
+            .find(|method| method.fragment == fragment)
+    }
+
+    /// The absolute identifier of a verification method, e.g. to use as a JWS `kid`.
+    pub fn verification_method_id(&self, method: &VerificationMethod) -> String {
+        self.absolute(&method.fragment)
+    }
+
+    /// The services of this document.
+    pub fn services(&self) -> &[DidService] {
+        &self.services
+    }
+
+    /// Parse a DID document, without checking its keys.
+    ///
+    /// Relationships must refer to verification methods of the document;
+    /// embedded verification methods are not supported.
+    pub fn from_json(doc: &Value) -> Result<DidDocument> {
+        let doc = doc
+            .as_object()
+            .ok_or(PError::InvalidDidDocument("not a JSON object"))?;
+        let id = doc
+            .get("id")
+            .and_then(Value::as_str)
+            .ok_or(PError::MissingField("id"))?;
+        let contexts = string_set(
+            doc.get("@context")
+                .ok_or(PError::MissingField("@context"))?,
+            "@context must only contain URIs",
+        )?;
+        if contexts.first() != Some(&DID_CONTEXT) {
+            return Err(PError::InvalidDidDocument(
+                "the first context must be the DID context",
+            ));
+        }
+        let mut did = DidDocument::new(id);
+        if let Some(controllers) = doc.get("controller") {
+            for controller in string_set(controllers, "controller must be a DID or a set of DIDs")?
+            {
+                did = did.controller(controller);
+            }
+        }
+        if let Some(uris) = doc.get("alsoKnownAs") {
+            for uri in string_set(uris, "alsoKnownAs must be a set of URIs")? {
+                did = did.also_known_as(uri);
+            }
+        }
+        if let Some(methods) = doc.get("verificationMethod") {
+            let methods = methods.as_array().ok_or(PError::InvalidDidDocument(
+                "verificationMethod must be a set",
+            ))?;
+            for method in methods {
+                let method = VerificationMethod::from_json(method, id)?;
+                if did
+                    .verification_methods()
+                    .any(|m| m.fragment == method.fragment)
+                {
+                    return Err(PError::InvalidDidDocument("duplicate verification method"));
+                }
+                did = did.verification_method(method, &[]);
+            }
+        }
+        for relationship in VerificationRelationship::ALL.iter() {
+            let refs = match doc.get(relationship.property()) {
+                Some(refs) => string_set(refs, "relationships must refer to verification methods")?,
+                None => continue,
+            };
+            for method_ref in refs {
+                let fragment = parse_fragment(method_ref, id)?;
+                let (_, relationships) = did
+                    .verification_methods
+                    .iter_mut()
+                    .find(|(method, _)| method.fragment == fragment)
+                    .ok_or(PError::InvalidDidDocument(
+                        "relationship to an unknown verification method",
+                    ))?;
+                if !relationships.contains(relationship) {
+                    relationships.push(*relationship);
+                }
+            }
+        }
+        if let Some(services) = doc.get("service") {
+            let services = services
+                .as_array()
+                .ok_or(PError::InvalidDidDocument("service must be a set"))?;
+            for service in services {
+                did = did.service(DidService::from_json(service, id)?);
+            }
+        }
+        // contexts implied by the verification method types are added back when serializing
+        for context in &contexts[1..] {
+            if !did
+                .verification_methods()
+                .any(|method| method.context() == Some(*context))
+            {
+                did = did.context(context);
+            }
+        }
+        Ok(did)
+    }
+
+    /// Resolve a `did:onion` identifier, from a document fetched by the caller.
+    ///
+    /// The document may come from an untrusted source, so every verification
+    /// method must be bound to the identity key encoded in the DID: Ed25519
+    /// keys must be that key, X25519 keys its Montgomery form, and the fragment
+    /// of each method must be the SHA-256 hash of its key; for JWKs, that is
+    /// the RFC 7638 thumbprint, whatever the order and extra members of the
+    /// published JWK. Documents with legacy key agreement keys cannot be
+    /// checked, and are rejected. So are documents and methods controlled by
+    /// another DID.
+    ///
+    /// Only the keys are authenticated: the `alsoKnownAs` URIs and the services
+    /// of the returned document are whatever the source of the document claims.
+    pub fn resolve_onion(did: &str, document: &str) -> Result<DidDocument> {
+        let pk = parse_onion_did(did)?.public_key();
+        let doc: Value = serde_json::from_str(document)
+            .map_err(|_| PError::InvalidDidDocument("invalid JSON"))?;
+        let doc = DidDocument::from_json(&doc)?;
+        if doc.id != did {
+            return Err(PError::InvalidDidDocument(
+                "the document is about another DID",
+            ));
+        }
+        if doc.controllers.iter().any(|controller| controller != did) {
+            return Err(PError::InvalidDidDocument(
+                "the document is controlled by another DID",
+            ));
+        }
+        for method in doc.verification_methods() {
+            if method.controller.is_some() {
+                return Err(PError::InvalidDidDocument(
+                    "a verification method is controlled by another DID",
+                ));
+            }
+            let key = method.key()?;
+            // hash the key as this crate serializes it, not as it was published
+            let expected = match (&key, method.property.as_str()) {
+                (VerificationKey::Ed25519(key), "publicKeyJwk") => key.jwk_thumbprint(),
+                (VerificationKey::X25519(key), "publicKeyJwk") => {
+                    VerificationMethod::x25519_jwk(key).fragment
+                }
+                _ => {
+                    VerificationMethod::new(
+                        &method.method_type,
+                        &method.property,
+                        method.material.clone(),
+                    )
+                    .fragment
+                }
+            };
+            if expected != method.fragment {
+                return Err(PError::DidFragmentMismatch(doc.absolute(&method.fragment)));
+            }
+            let matches = match key {
+                VerificationKey::Ed25519(key) => key == pk,
+                VerificationKey::X25519(key) => key == pk.to_x25519()?,
+            };
+            if !matches {
+                return Err(PError::DidKeyMismatch(doc.absolute(&method.fragment)));
+            }
+        }
+        Ok(doc)
+    }
+
+    fn absolute(&self, fragment: &str) -> String {
+        format!("{}#{}", self.id, fragment)
+    }
+
+    /// The JSON-LD representation of this document.
+    pub fn to_json(&self) -> Value {
+        let mut contexts = vec![DID_CONTEXT.to_string()];
+        let method_contexts = self
+            .verification_methods()
+            .filter_map(VerificationMethod::context)
+            .map(str::to_string);
+        for context in method_contexts.chain(self.contexts.iter().cloned()) {
+            if !contexts.contains(&context) {
+                contexts.push(context);
+            }
+        }
+
+        let mut doc = Map::new();
+        doc.insert("@context".to_string(), json!(contexts));
+        doc.insert("id".to_string(), json!(self.id));
+        match &self.controllers[..] {
+            [] => {}
+            [controller] => {
+                doc.insert("controller".to_string(), json!(controller));
+            }
+            controllers => {
+                doc.insert("controller".to_string(), json!(controllers));
+            }
+        }
+        if !self.also_known_as.is_empty() {
+            doc.insert("alsoKnownAs".to_string(), json!(self.also_known_as));
+        }
+        if !self.verification_methods.is_empty() {
+            let methods: Vec<Value> = self
+                .verification_methods()
+                .map(|method| {
+                    let mut m = Map::new();
+                    m.insert("id".to_string(), json!(self.absolute(&method.fragment)));
+                    m.insert("type".to_string(), json!(method.method_type));
+                    m.insert(
+                        "controller".to_string(),
+                        json!(method.controller.as_ref().unwrap_or(&self.id)),
+                    );
+                    m.insert(method.property.clone(), method.material.clone());
+                    Value::Object(m)
+                })
+                .collect();
+            doc.insert("verificationMethod".to_string(), Value::Array(methods));
+        }
+        for relationship in VerificationRelationship::ALL.iter() {
+            let ids: Vec<String> = self
+                .verification_methods_for(*relationship)
+                .map(|method| self.absolute(&method.fragment))
+                .collect();
+            if !ids.is_empty() {
+                doc.insert(relationship.property().to_string(), json!(ids));
+            }
+        }
+        if !self.services.is_empty() {
+            let services: Vec<Value> = self
+                .services
+                .iter()
+                .map(|service| {
+                    json!({
+                        "id": self.absolute(&service.fragment),
+                        "type": service.service_type,
+                        "serviceEndpoint": service.endpoint,
+                    })
+                })
+                .collect();
+            doc.insert("service".to_string(), Value::Array(services));
+        }
+        Value::Object(doc)
+    }
+}
+
+impl fmt::Display for DidDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let json = serde_json::to_string_pretty(&self.to_json()).map_err(|_| fmt::Error)?;
+        f.write_str(&json)
+    }
+}
@@ -1,4 +1,5 @@
 use crate::base64;
+use crate::kdf_params::KdfAlgorithm;
 use std::error::Error as StdError;
 use std::{self, fmt, io};
 
@@ -11,7 +12,9 @@ macro_rules! werr(
 
 pub type Result<T> = std::result::Result<T, PError>;
 
-#[derive(Debug)]
+/// Broad category of an error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum ErrorKind {
     Generate,
     Sign,
@@ -24,11 +27,102 @@ pub enum ErrorKind {
     Encoding,
 }
 
-/// Error structure for the `minisign` crate.
+/// Error type for the `minisign` crate.
+///
+/// Failures that callers may want to react to get their own variant;
+/// everything else is carried by `Other`, along with its `ErrorKind`.
 #[derive(Debug)]
-pub struct PError {
-    kind: ErrorKind,
-    err: Box<dyn StdError + Send + Sync>,
+#[non_exhaustive]
+pub enum PError {
+    /// The password doesn't decrypt the secret key.
+    WrongPassword,
+    /// The checksum of an unencrypted secret key doesn't match the key.
+    InvalidChecksum,
+    /// The password and its confirmation are different.
+    PasswordMismatch,
+    /// The password is longer than `PASSWORD_MAXBYTES`.
+    PasswordTooLong,
+    /// A password provider that has no password to give.
+    PasswordUnavailable(String),
+    /// The signature was made with a different key than the public key.
+    KeyIdMismatch { sig: u64, pk: u64 },
+    /// The public key given for signing doesn't match the secret key.
+    PublicKeyMismatch { pk: u64 },
+    /// Unknown signature algorithm.
+    UnsupportedAlgorithm([u8; 2]),
+    /// Unknown key derivation algorithm.
+    UnsupportedKdf([u8; 2]),
+    /// The key derivation parameters exceed the supported limits.
+    KdfParamsTooHigh(KdfAlgorithm),
+    /// A seed that is not 32 bytes long.
+    InvalidSeedLength(usize),
+    /// A public key that is not a valid Ed25519 point.
+    InvalidPublicKey,
+    /// A base64-encoded key of the wrong length.
+    InvalidBase64Length { expected: usize, got: usize },
+    /// Base64 data that cannot be decoded, and what it should have been.
+    InvalidBase64(&'static str),
+    /// A required line or field is missing from an encoded key or signature.
+    MissingField(&'static str),
+    /// An onion address of the wrong length.
+    InvalidOnionLength(usize),
+    /// An onion address that isn't valid base32.
+    InvalidOnionEncoding,
+    /// An onion address with a version other than 3.
+    InvalidOnionVersion(u8),
+    /// An onion address whose checksum doesn't match its public key.
+    InvalidOnionChecksum,
+    /// A file that is not a Tor `hs_ed25519_secret_key` or `hs_ed25519_public_key`.
+    InvalidTorKey,
+    /// A malformed Tor client authorization file.
+    InvalidTorClientAuth(&'static str),
+    /// A malformed or unsupported Tor ed25519 certificate.
+    InvalidTorCert(&'static str),
+    /// The signature of a Tor ed25519 certificate doesn't match its signing key.
+    TorCertSignatureInvalid,
+    /// The operation needs the seed, which an expanded secret key doesn't have.
+    SeedRequired,
+    /// An onion service time period length of zero minutes.
+    InvalidTimePeriodLength,
+    /// A SLIP10 derivation path that cannot be parsed.
+    InvalidSlip10Path(String),
+    /// A SLIP10 key that cannot be derived.
+    Slip10Derivation,
+    /// A vanity search pattern that no key can match.
+    InvalidVanityPattern(&'static str),
+    /// A DID that cannot be parsed, or that uses an unsupported method.
+    InvalidDid(&'static str),
+    /// A DID document that cannot be parsed.
+    InvalidDidDocument(&'static str),
+    /// The key of a verification method doesn't match the DID.
+    DidKeyMismatch(String),
+    /// The identifier of a verification method doesn't match its key.
+    DidFragmentMismatch(String),
+    /// A JSON Web Signature that cannot be parsed or is not supported.
+    InvalidJws(&'static str),
+    /// The JSON Web Signature doesn't match the payload.
+    JwsSignatureInvalid,
+    /// A JSON Web Key that cannot be parsed or is not an Ed25519 key.
+    InvalidJwk(&'static str),
+    /// A verifiable credential or presentation that cannot be parsed or is not supported.
+    InvalidCredential(&'static str),
+    /// A data integrity proof that cannot be parsed or is not supported.
+    InvalidProof(&'static str),
+    /// The data integrity proof doesn't match the document.
+    ProofSignatureInvalid,
+    /// The signature doesn't match the data.
+    SignatureInvalid,
+    /// The global signature doesn't match the trusted comment.
+    TrustedCommentSignatureInvalid,
+    /// The trusted comment and its global signature are not both present.
+    TrustedCommentMissing,
+    /// An I/O error.
+    Io(io::Error),
+    /// Any other error.
+    Other {
+        kind: ErrorKind,
+        err: Box<dyn StdError + Send + Sync>,
+    },
 }
 
 impl PError {
@@ -41,47 +135,186 @@ impl PError {
     where
         E: Into<Box<dyn StdError + Send + Sync>>,
     {
-        PError {
+        PError::Other {
             kind,
             err: err.into(),
         }
     }
+
+    /// The broad category of this error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            PError::WrongPassword => ErrorKind::Verify,
+            PError::InvalidChecksum => ErrorKind::Verify,
+            PError::PasswordMismatch => ErrorKind::Generate,
+            PError::PasswordTooLong => ErrorKind::Misc,
+            PError::PasswordUnavailable(_) => ErrorKind::Io,
+            PError::KeyIdMismatch { .. } => ErrorKind::Verify,
+            PError::PublicKeyMismatch { .. } => ErrorKind::Sign,
+            PError::UnsupportedAlgorithm(_) => ErrorKind::Verify,
+            PError::UnsupportedKdf(_) => ErrorKind::KDF,
+            PError::KdfParamsTooHigh(_) => ErrorKind::KDF,
+            PError::InvalidSeedLength(_) => ErrorKind::Generate,
+            PError::InvalidPublicKey => ErrorKind::Encoding,
+            PError::InvalidBase64Length { .. } => ErrorKind::Encoding,
+            PError::InvalidBase64(_) => ErrorKind::Encoding,
+            PError::MissingField(_) => ErrorKind::Encoding,
+            PError::InvalidOnionLength(_) => ErrorKind::Encoding,
+            PError::InvalidOnionEncoding => ErrorKind::Encoding,
+            PError::InvalidOnionVersion(_) => ErrorKind::Encoding,
+            PError::InvalidOnionChecksum => ErrorKind::Encoding,
+            PError::InvalidTorKey => ErrorKind::Encoding,
+            PError::InvalidTorClientAuth(_) => ErrorKind::Encoding,
+            PError::InvalidTorCert(_) => ErrorKind::Encoding,
+            PError::TorCertSignatureInvalid => ErrorKind::Verify,
+            PError::SeedRequired => ErrorKind::Misc,
+            PError::InvalidTimePeriodLength => ErrorKind::Misc,
+            PError::InvalidSlip10Path(_) => ErrorKind::Generate,
+            PError::Slip10Derivation => ErrorKind::Generate,
+            PError::InvalidVanityPattern(_) => ErrorKind::Generate,
+            PError::InvalidDid(_) => ErrorKind::Encoding,
+            PError::InvalidDidDocument(_) => ErrorKind::Encoding,
+            PError::DidKeyMismatch(_) => ErrorKind::Verify,
+            PError::DidFragmentMismatch(_) => ErrorKind::Verify,
+            PError::InvalidJws(_) => ErrorKind::Encoding,
+            PError::JwsSignatureInvalid => ErrorKind::Verify,
+            PError::InvalidJwk(_) => ErrorKind::Encoding,
+            PError::InvalidCredential(_) => ErrorKind::Encoding,
+            PError::InvalidProof(_) => ErrorKind::Encoding,
+            PError::ProofSignatureInvalid => ErrorKind::Verify,
+            PError::SignatureInvalid => ErrorKind::Verify,
+            PError::TrustedCommentSignatureInvalid => ErrorKind::Verify,
+            PError::TrustedCommentMissing => ErrorKind::Verify,
+            PError::Io(_) => ErrorKind::Io,
+            PError::Other { kind, .. } => *kind,
+        }
+    }
 }
 
 impl fmt::Display for PError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self.kind {
-            ErrorKind::Generate => write!(f, "{}", self.err),
-            ErrorKind::Sign => write!(f, "{}", self.err),
-            ErrorKind::Verify => write!(f, "{}", self.err),
-            ErrorKind::Misc => write!(f, "{}", self.err),
-            ErrorKind::Io => write!(f, "{}", self.err),
-            ErrorKind::Hash => write!(f, "{}", self.err),
-            ErrorKind::KDF => write!(f, "{}", self.err),
-            ErrorKind::RNG => write!(f, "{}", self.err),
-            ErrorKind::Encoding => write!(f, "{}", self.err),
+        match self {
+            PError::WrongPassword => write!(f, "Wrong password for that key"),
+            PError::InvalidChecksum => write!(f, "Secret key checksum mismatch"),
+            PError::PasswordMismatch => write!(f, "passwords don't match!"),
+            PError::PasswordTooLong => write!(f, "passphrase can't exceed 1024 bytes length"),
+            PError::PasswordUnavailable(reason) => write!(f, "Cannot read password: {}", reason),
+            PError::KeyIdMismatch { sig, pk } => write!(
+                f,
+                "Signature key id: {:X} is different from public key: {:X}",
+                sig, pk
+            ),
+            PError::PublicKeyMismatch { pk } => write!(
+                f,
+                "Could not verify signature with the provided public key ID: {:X}",
+                pk
+            ),
+            PError::UnsupportedAlgorithm(alg) => write!(
+                f,
+                "Unsupported signature algorithm: {:02x}{:02x}",
+                alg[0], alg[1]
+            ),
+            PError::UnsupportedKdf(alg) => write!(
+                f,
+                "Unsupported key derivation algorithm: {:02x}{:02x}",
+                alg[0], alg[1]
+            ),
+            PError::KdfParamsTooHigh(KdfAlgorithm::Scrypt) => {
+                write!(f, "scrypt parameters too high")
+            }
+            PError::KdfParamsTooHigh(KdfAlgorithm::Argon2id) => {
+                write!(f, "Argon2id parameters too high")
+            }
+            PError::InvalidSeedLength(len) => {
+                write!(f, "Seed must be 32 bytes long, got {} bytes", len)
+            }
+            PError::InvalidPublicKey => write!(f, "Invalid public key"),
+            PError::InvalidBase64Length { expected, got } => write!(
+                f,
+                "Base64 conversion failed - expected {} characters, got {}",
+                expected, got
+            ),
+            PError::InvalidBase64(what) => {
+                write!(
+                    f,
+                    "Base64 conversion failed - was an actual {} given?",
+                    what
+                )
+            }
+            PError::MissingField(field) => write!(f, "Missing {}", field),
+            PError::InvalidOnionLength(len) => {
+                write!(f, "Invalid onion address length: {}", len)
+            }
+            PError::InvalidOnionEncoding => write!(f, "Cannot decode onion address"),
+            PError::InvalidOnionVersion(version) => {
+                write!(f, "Onion version incorrect: {}", version)
+            }
+            PError::InvalidOnionChecksum => write!(f, "Onion checksum incorrect"),
+            PError::InvalidTorKey => write!(f, "Not a Tor ed25519v1 key file"),
+            PError::InvalidTorClientAuth(reason) => {
+                write!(f, "Invalid Tor client authorization: {}", reason)
+            }
+            PError::InvalidTorCert(reason) => write!(f, "Invalid Tor certificate: {}", reason),
+            PError::TorCertSignatureInvalid => {
+                write!(f, "Tor certificate signature verification failed")
+            }
+            PError::SeedRequired => write!(
+                f,
+                "This operation requires a seed, not an expanded secret key"
+            ),
+            PError::InvalidTimePeriodLength => {
+                write!(f, "The time period length must not be zero")
+            }
+            PError::InvalidSlip10Path(path) => write!(f, "Incorrect SLIP10 chain: {}", path),
+            PError::Slip10Derivation => write!(f, "Cannot derive SLIP10 keys"),
+            PError::InvalidVanityPattern(reason) => {
+                write!(f, "Invalid vanity pattern: {}", reason)
+            }
+            PError::InvalidDid(reason) => write!(f, "Invalid DID: {}", reason),
+            PError::InvalidDidDocument(reason) => write!(f, "Invalid DID document: {}", reason),
+            PError::DidKeyMismatch(id) => write!(
+                f,
+                "The key of verification method {} doesn't match the DID",
+                id
+            ),
+            PError::DidFragmentMismatch(id) => write!(
+                f,
+                "The identifier of verification method {} doesn't match its key",
+                id
+            ),
+            PError::InvalidJws(reason) => write!(f, "Invalid JWS: {}", reason),
+            PError::JwsSignatureInvalid => write!(f, "JWS signature verification failed"),
+            PError::InvalidJwk(reason) => write!(f, "Invalid JWK: {}", reason),
+            PError::InvalidCredential(reason) => write!(f, "Invalid credential: {}", reason),
+            PError::InvalidProof(reason) => write!(f, "Invalid proof: {}", reason),
+            PError::ProofSignatureInvalid => write!(f, "Proof verification failed"),
+            PError::SignatureInvalid => write!(f, "Signature verification failed"),
+            PError::TrustedCommentSignatureInvalid => {
+                write!(f, "Comment signature verification failed")
+            }
+            PError::TrustedCommentMissing => write!(
+                f,
+                "Inconsistent signature presence for trusted comment presence"
+            ),
+            PError::Io(err) => write!(f, "{}", err),
+            PError::Other { err, .. } => write!(f, "{}", err),
         }
     }
 }
+
 impl StdError for PError {
-    fn description(&self) -> &str {
-        match self.kind {
-            ErrorKind::Generate => "generate error",
-            ErrorKind::Sign => "sign error",
-            ErrorKind::Verify => "verify error",
-            ErrorKind::Misc => "misc error",
-            ErrorKind::Io => "io error",
-            ErrorKind::Hash => "hash error",
-            ErrorKind::KDF => "key derivation error",
-            ErrorKind::RNG => "random number generator error",
-            ErrorKind::Encoding => "encoding error",
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            PError::Io(err) => Some(err),
+            PError::Other { err, .. } => Some(err.as_ref()),
+            _ => None,
         }
     }
 }
 
 impl From<io::Error> for PError {
     fn from(err: io::Error) -> PError {
-        PError::new(ErrorKind::Io, err)
+        PError::Io(err)
     }
 }
 
@@ -115,6 +348,12 @@ impl From<scrypt::errors::InvalidOutputLen> for PError {
     }
 }
 
+impl From<argon2::Error> for PError {
+    fn from(err: argon2::Error) -> PError {
+        PError::new(ErrorKind::KDF, err.to_string())
+    }
+}
+
 impl From<getrandom::Error> for PError {
     fn from(err: getrandom::Error) -> PError {
         PError::new(ErrorKind::RNG, format!("{}", err))
@@ -1,18 +1,21 @@
 use crate::constants::*;
 use crate::errors::*;
+use crate::kdf_params::KdfAlgorithm;
+use crate::password::Password;
 use scrypt::ScryptParams;
 use std::cmp;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+// prompts go to stderr, so that they don't mix with piped output
 #[cfg(any(windows, unix))]
-use rpassword::prompt_password_stdout;
+use rpassword::prompt_password_stderr;
 
 #[cfg(not(any(windows, unix)))]
-fn prompt_password_stdout(prompt: &str) -> Result<String> {
-    use std::io::{stdin, stdout, Write};
+fn prompt_password_stderr(prompt: &str) -> Result<String> {
+    use std::io::{stderr, stdin, Write};
 
-    stdout().write_all(prompt.as_bytes())?;
-    stdout().flush()?;
+    stderr().write_all(prompt.as_bytes())?;
+    stderr().flush()?;
     let mut password = String::new();
     stdin().read_line(&mut password)?;
     Ok(password)
@@ -64,28 +67,22 @@ pub fn raw_scrypt_params(memlimit: usize, opslimit: u64, n_log2_max: u8) -> Resu
             }
             n_log2 += 1;
         }
-        let maxrp = cmp::min(
-            0x3fff_ffff as u32,
-            ((opslimit / 4) / (1u64 << n_log2)) as u32,
-        );
+        let maxrp = cmp::min(0x3fff_ffff_u32, ((opslimit / 4) / (1u64 << n_log2)) as u32);
         p = maxrp / r;
     }
     if n_log2 > n_log2_max {
-        return Err(PError::new(ErrorKind::KDF, "scrypt parameters too high"));
+        return Err(PError::KdfParamsTooHigh(KdfAlgorithm::Scrypt));
     }
     ScryptParams::new(n_log2, r, p).map_err(Into::into)
 }
 
-pub fn get_password(prompt: &str) -> Result<String> {
-    let pwd = prompt_password_stdout(prompt)?;
+pub fn get_password(prompt: &str) -> Result<Password> {
+    let pwd = Password::from(prompt_password_stderr(prompt)?);
     if pwd.is_empty() {
-        println!("<empty>");
+        eprintln!("<empty>");
         Ok(pwd)
     } else if pwd.len() > PASSWORD_MAXBYTES {
-        Err(PError::new(
-            ErrorKind::Misc,
-            "passphrase can't exceed 1024 bytes length",
-        ))
+        Err(PError::PasswordTooLong)
     } else {
         Ok(pwd)
     }
@@ -98,3 +95,28 @@ pub fn unix_timestamp() -> u64 {
         .expect("system clock is incorrect");
     since_the_epoch.as_secs()
 }
+
+/// Format a Unix timestamp as an XML Schema `dateTime` in UTC, e.g. `2021-02-03T04:05:06Z`.
+pub fn xsd_date_time(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let secs = timestamp % 86400;
+    // civil from days, http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
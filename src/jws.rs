@@ -0,0 +1,284 @@
+use crate::crypto::ed25519;
+use crate::did::*;
+use crate::errors::*;
+use crate::public_key::*;
+use crate::secret_key::*;
+use data_encoding::base64url;
+use serde_json::{json, Map, Value};
+
+const JWS_ALG: &str = "EdDSA";
+
+fn b64_decode(s: &str) -> Result<Vec<u8>> {
+    base64url::decode_nopad(s.as_bytes())
+        .map_err(|_| PError::InvalidJws("invalid base64url encoding"))
+}
+
+fn str_field<'a>(value: &'a Value, name: &'static str) -> Result<&'a str> {
+    value
+        .get(name)
+        .and_then(Value::as_str)
+        .ok_or(PError::MissingField(name))
+}
+
+/// A JSON Web Signature (RFC 7515) with the `EdDSA` algorithm (RFC 8037).
+///
+/// Detached signatures use the unencoded payload option (RFC 7797): the
+/// payload is signed as is, and is not included in the serialized JWS.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Jws {
+    protected: String,
+    header: Map<String, Value>,
+    payload: Option<Vec<u8>>,
+    signature: [u8; 64],
+}
+
+impl Jws {
+    /// Sign `payload`, which is included in the JWS.
+    ///
+    /// `kid` is usually the id of a DID verification method, see
+    /// `DidDocument::verification_method_id()`.
+    pub fn sign(payload: &[u8], sk: &SecretKey, kid: Option<&str>) -> Result<Jws> {
+        let mut header = Map::new();
+        header.insert("alg".to_string(), json!(JWS_ALG));
+        Jws::sign_with_header(header, payload, sk, kid, false)
+    }
+
+    /// Sign `payload` as an unencoded, detached payload.
+    ///
+    /// The verifier must be given the same payload.
+    pub fn sign_detached(payload: &[u8], sk: &SecretKey, kid: Option<&str>) -> Result<Jws> {
+        let mut header = Map::new();
+        header.insert("alg".to_string(), json!(JWS_ALG));
+        header.insert("b64".to_string(), json!(false));
+        header.insert("crit".to_string(), json!(["b64"]));
+        Jws::sign_with_header(header, payload, sk, kid, true)
+    }
+
+    fn sign_with_header(
+        mut header: Map<String, Value>,
+        payload: &[u8],
+        sk: &SecretKey,
+        kid: Option<&str>,
+        detached: bool,
+    ) -> Result<Jws> {
+        if let Some(kid) = kid {
+            header.insert("kid".to_string(), json!(kid));
+        }
+        let protected =
+            base64url::encode_nopad(Value::Object(header.clone()).to_string().as_bytes());
+        let mut jws = Jws {
+            protected,
+            header,
+            payload: if detached {
+                None
+            } else {
+                Some(payload.to_vec())
+            },
+            signature: [0u8; 64],
+        };
+        jws.signature = sk.sign_raw(&jws.signing_input(payload)?, None);
+        Ok(jws)
+    }
+
+    /// The protected header.
+    pub fn header(&self) -> &Map<String, Value> {
+        &self.header
+    }
+
+    /// The `kid` header parameter, identifying the signing key.
+    pub fn kid(&self) -> Option<&str> {
+        self.header.get("kid").and_then(Value::as_str)
+    }
+
+    /// The payload, unless it is detached.
+    pub fn payload(&self) -> Option<&[u8]> {
+        self.payload.as_deref()
+    }
+
+    /// Returns `true` if the payload is not included in the JWS.
+    pub fn is_detached(&self) -> bool {
+        self.payload.is_none()
+    }
+
+    fn is_encoded(&self) -> Result<bool> {
+        let b64 = match self.header.get("b64") {
+            None => return Ok(true),
+            Some(Value::Bool(b64)) => *b64,
+            Some(_) => return Err(PError::InvalidJws("b64 must be a boolean")),
+        };
+        let crit = self.header.get("crit").and_then(Value::as_array);
+        if !crit.is_some_and(|crit| crit.contains(&json!("b64"))) {
+            return Err(PError::InvalidJws("b64 must be listed as critical"));
+        }
+        Ok(b64)
+    }
+
+    fn encoded_payload(&self, payload: &[u8]) -> Result<String> {
+        if self.is_encoded()? {
+            Ok(base64url::encode_nopad(payload))
+        } else {
+            String::from_utf8(payload.to_vec()).map_err(|_| {
+                PError::InvalidJws("unencoded payloads must be UTF-8 to be serialized")
+            })
+        }
+    }
+
+    fn signing_input(&self, payload: &[u8]) -> Result<Vec<u8>> {
+        let mut input = self.protected.as_bytes().to_vec();
+        input.push(b'.');
+        if self.is_encoded()? {
+            input.extend_from_slice(base64url::encode_nopad(payload).as_bytes());
+        } else {
+            input.extend_from_slice(payload);
+        }
+        Ok(input)
+    }
+
+    /// Verify the signature with `pk`.
+    ///
+    /// `detached_payload` must be provided if, and only if, the payload is detached.
+    pub fn verify(&self, pk: &PublicKey, detached_payload: Option<&[u8]>) -> Result<()> {
+        if self.header.get("alg") != Some(&json!(JWS_ALG)) {
+            return Err(PError::InvalidJws("unsupported algorithm"));
+        }
+        if let Some(crit) = self.header.get("crit") {
+            let crit = crit
+                .as_array()
+                .ok_or(PError::InvalidJws("crit must be an array"))?;
+            if crit.iter().any(|param| param != "b64") {
+                return Err(PError::InvalidJws("unsupported critical header parameter"));
+            }
+        }
+        let payload = match (&self.payload, detached_payload) {
+            (Some(payload), None) => &payload[..],
+            (None, Some(payload)) => payload,
+            (Some(_), Some(_)) => return Err(PError::InvalidJws("the payload is not detached")),
+            (None, None) => return Err(PError::InvalidJws("missing detached payload")),
+        };
+        if !ed25519::verify(
+            &self.signing_input(payload)?,
+            &pk.keynum_pk.pk,
+            &self.signature,
+        ) {
+            return Err(PError::JwsSignatureInvalid);
+        }
+        Ok(())
+    }
+
+    /// Verify the signature with the key of the verification method named by
+    /// `kid` in `did_document`.
+    ///
+    /// The document should have been resolved first, e.g. with
+    /// `DidDocument::resolve_onion()`.
+    pub fn verify_with_did(
+        &self,
+        did_document: &DidDocument,
+        detached_payload: Option<&[u8]>,
+    ) -> Result<()> {
+        let kid = self.kid().ok_or(PError::MissingField("kid"))?;
+        let method = did_document
+            .find_verification_method(kid)
+            .ok_or(PError::InvalidJws(
+                "kid is not a verification method of the DID",
+            ))?;
+        match method.key()? {
+            VerificationKey::Ed25519(pk) => self.verify(&pk, detached_payload),
+            VerificationKey::X25519(_) => Err(PError::InvalidJws("kid is not a signing key")),
+        }
+    }
+
+    /// The compact serialization: `header.payload.signature`, or
+    /// `header..signature` if the payload is detached.
+    pub fn to_compact(&self) -> Result<String> {
+        let payload = match &self.payload {
+            Some(payload) => self.encoded_payload(payload)?,
+            None => String::new(),
+        };
+        if payload.contains('.') {
+            return Err(PError::InvalidJws(
+                "unencoded payloads with a period cannot use the compact serialization",
+            ));
+        }
+        Ok(format!(
+            "{}.{}.{}",
+            self.protected,
+            payload,
+            base64url::encode_nopad(&self.signature)
+        ))
+    }
+
+    /// Parse the compact serialization.
+    pub fn from_compact(s: &str) -> Result<Jws> {
+        let parts: Vec<&str> = s.trim().split('.').collect();
+        if parts.len() != 3 {
+            return Err(PError::InvalidJws("a compact JWS has three parts"));
+        }
+        let payload = Some(parts[1]).filter(|payload| !payload.is_empty());
+        Jws::from_parts(parts[0], payload, parts[2])
+    }
+
+    /// The flattened JSON serialization.
+    pub fn to_json(&self) -> Result<Value> {
+        let mut jws = Map::new();
+        jws.insert("protected".to_string(), json!(self.protected));
+        if let Some(payload) = &self.payload {
+            jws.insert("payload".to_string(), json!(self.encoded_payload(payload)?));
+        }
+        jws.insert(
+            "signature".to_string(),
+            json!(base64url::encode_nopad(&self.signature)),
+        );
+        Ok(Value::Object(jws))
+    }
+
+    /// Parse the flattened JSON serialization, or a general one with a single signature.
+    pub fn from_json(jws: &Value) -> Result<Jws> {
+        let signature = match jws.get("signatures").and_then(Value::as_array) {
+            Some(signatures) if signatures.len() == 1 => &signatures[0],
+            Some(_) => return Err(PError::InvalidJws("only single signatures are supported")),
+            None => jws,
+        };
+        if signature.get("header").is_some() {
+            return Err(PError::InvalidJws("unprotected headers are not supported"));
+        }
+        let payload = jws
+            .get("payload")
+            .map(|payload| {
+                payload
+                    .as_str()
+                    .ok_or(PError::InvalidJws("invalid payload"))
+            })
+            .transpose()?;
+        Jws::from_parts(
+            str_field(signature, "protected")?,
+            payload,
+            str_field(signature, "signature")?,
+        )
+    }
+
+    fn from_parts(protected: &str, payload: Option<&str>, signature: &str) -> Result<Jws> {
+        let header: Value = serde_json::from_slice(&b64_decode(protected)?)
+            .map_err(|_| PError::InvalidJws("invalid header"))?;
+        let header = match header {
+            Value::Object(header) => header,
+            _ => return Err(PError::InvalidJws("invalid header")),
+        };
+        let mut jws = Jws {
+            protected: protected.to_string(),
+            header,
+            payload: None,
+            signature: [0u8; 64],
+        };
+        let signature = b64_decode(signature)?;
+        if signature.len() != jws.signature.len() {
+            return Err(PError::InvalidJws("invalid signature length"));
+        }
+        jws.signature.copy_from_slice(&signature);
+        jws.payload = match payload {
+            Some(payload) if jws.is_encoded()? => Some(b64_decode(payload)?),
+            Some(payload) => Some(payload.as_bytes().to_vec()),
+            None => None,
+        };
+        Ok(jws)
+    }
+}
@@ -0,0 +1,195 @@
+use crate::constants::*;
+use crate::errors::*;
+use crate::helpers::*;
+use argon2::{Algorithm, Argon2, Params, Version};
+
+/// A key derivation function that can protect a secret key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KdfAlgorithm {
+    /// scrypt, as used by the reference implementation.
+    Scrypt,
+    /// Argon2id, with a single lane.
+    Argon2id,
+}
+
+impl KdfAlgorithm {
+    pub(crate) fn from_tag(kdf_alg: [u8; TWOBYTES]) -> Result<KdfAlgorithm> {
+        match kdf_alg {
+            KDF_ALG => Ok(KdfAlgorithm::Scrypt),
+            KDF_ALG_ARGON2ID => Ok(KdfAlgorithm::Argon2id),
+            _ => Err(PError::UnsupportedKdf(kdf_alg)),
+        }
+    }
+
+    pub(crate) fn tag(self) -> [u8; TWOBYTES] {
+        match self {
+            KdfAlgorithm::Scrypt => KDF_ALG,
+            KdfAlgorithm::Argon2id => KDF_ALG_ARGON2ID,
+        }
+    }
+}
+
+/// Cost parameters of the key derivation function used to encrypt a secret key.
+///
+/// These are stored alongside the secret key, so that the same parameters
+/// can be used to decrypt it.
+///
+/// For scrypt, the limits have the same meaning as in the reference implementation.
+/// For Argon2id, `opslimit` is the number of passes, and `memlimit` the amount
+/// of memory to use, in bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KdfParams {
+    pub(crate) alg: KdfAlgorithm,
+    pub(crate) opslimit: u64,
+    pub(crate) memlimit: usize,
+}
+
+impl KdfParams {
+    /// Parameters for keys that are unlocked often, or on constrained devices.
+    pub fn interactive() -> KdfParams {
+        KdfParams {
+            alg: KdfAlgorithm::Scrypt,
+            opslimit: OPSLIMIT_INTERACTIVE,
+            memlimit: MEMLIMIT_INTERACTIVE,
+        }
+    }
+
+    /// The default parameters, compatible with the reference implementation.
+    pub fn moderate() -> KdfParams {
+        KdfParams {
+            alg: KdfAlgorithm::Scrypt,
+            opslimit: OPSLIMIT,
+            memlimit: MEMLIMIT,
+        }
+    }
+
+    /// Parameters for keys that are rarely unlocked, such as cold storage keys.
+    pub fn sensitive() -> KdfParams {
+        KdfParams {
+            alg: KdfAlgorithm::Scrypt,
+            opslimit: OPSLIMIT_SENSITIVE,
+            memlimit: MEMLIMIT_SENSITIVE,
+        }
+    }
+
+    /// Argon2id parameters for keys that are unlocked often.
+    pub fn argon2id_interactive() -> KdfParams {
+        KdfParams {
+            alg: KdfAlgorithm::Argon2id,
+            opslimit: ARGON2ID_OPSLIMIT_INTERACTIVE,
+            memlimit: ARGON2ID_MEMLIMIT_INTERACTIVE,
+        }
+    }
+
+    /// Argon2id parameters for general use.
+    pub fn argon2id_moderate() -> KdfParams {
+        KdfParams {
+            alg: KdfAlgorithm::Argon2id,
+            opslimit: ARGON2ID_OPSLIMIT_MODERATE,
+            memlimit: ARGON2ID_MEMLIMIT_MODERATE,
+        }
+    }
+
+    /// Argon2id parameters for keys that are rarely unlocked.
+    pub fn argon2id_sensitive() -> KdfParams {
+        KdfParams {
+            alg: KdfAlgorithm::Argon2id,
+            opslimit: ARGON2ID_OPSLIMIT_SENSITIVE,
+            memlimit: ARGON2ID_MEMLIMIT_SENSITIVE,
+        }
+    }
+
+    /// Create parameters from exact scrypt limits.
+    pub fn new(opslimit: u64, memlimit: usize) -> Result<KdfParams> {
+        KdfParams::with_algorithm(KdfAlgorithm::Scrypt, opslimit, memlimit)
+    }
+
+    /// Create parameters from exact Argon2id limits.
+    pub fn new_argon2id(opslimit: u64, memlimit: usize) -> Result<KdfParams> {
+        KdfParams::with_algorithm(KdfAlgorithm::Argon2id, opslimit, memlimit)
+    }
+
+    /// Create parameters for the given algorithm from exact limits.
+    pub fn with_algorithm(alg: KdfAlgorithm, opslimit: u64, memlimit: usize) -> Result<KdfParams> {
+        let kdf_params = KdfParams {
+            alg,
+            opslimit,
+            memlimit,
+        };
+        match alg {
+            KdfAlgorithm::Scrypt => {
+                kdf_params.scrypt_params()?;
+            }
+            KdfAlgorithm::Argon2id => {
+                kdf_params.argon2id_params(CHK_BYTES + SECRETKEY_BYTES + KEYNUM_BYTES)?;
+            }
+        }
+        Ok(kdf_params)
+    }
+
+    /// Replace the operations limit.
+    pub fn with_opslimit(self, opslimit: u64) -> Result<KdfParams> {
+        KdfParams::with_algorithm(self.alg, opslimit, self.memlimit)
+    }
+
+    /// Replace the memory limit, in bytes.
+    pub fn with_memlimit(self, memlimit: usize) -> Result<KdfParams> {
+        KdfParams::with_algorithm(self.alg, self.opslimit, memlimit)
+    }
+
+    /// The key derivation algorithm.
+    pub fn algorithm(&self) -> KdfAlgorithm {
+        self.alg
+    }
+
+    /// The operations limit.
+    pub fn opslimit(&self) -> u64 {
+        self.opslimit
+    }
+
+    /// The memory limit, in bytes.
+    pub fn memlimit(&self) -> usize {
+        self.memlimit
+    }
+
+    pub(crate) fn derive(&self, password: &[u8], salt: &[u8], out: &mut [u8]) -> Result<()> {
+        match self.alg {
+            KdfAlgorithm::Scrypt => {
+                let params = self.scrypt_params()?;
+                scrypt::scrypt(password, salt, &params, out)?;
+            }
+            KdfAlgorithm::Argon2id => {
+                let params = self.argon2id_params(out.len())?;
+                Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                    .hash_password_into(password, salt, out)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn scrypt_params(&self) -> Result<scrypt::ScryptParams> {
+        if self.memlimit > MEMLIMIT_MAX {
+            return Err(PError::KdfParamsTooHigh(KdfAlgorithm::Scrypt));
+        }
+        raw_scrypt_params(self.memlimit, self.opslimit, N_LOG2_MAX)
+    }
+
+    fn argon2id_params(&self, output_len: usize) -> Result<Params> {
+        if self.memlimit > MEMLIMIT_MAX || self.opslimit > u64::from(u32::MAX) {
+            return Err(PError::KdfParamsTooHigh(KdfAlgorithm::Argon2id));
+        }
+        Params::new(
+            (self.memlimit / 1024) as u32,
+            self.opslimit as u32,
+            1,
+            Some(output_len),
+        )
+        .map_err(Into::into)
+    }
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams::moderate()
+    }
+}
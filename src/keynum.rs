@@ -1,7 +1,9 @@
 use crate::constants::*;
 use crate::crypto::util::fixed_time_eq;
+use crate::helpers::load_u64_le;
 use std::cmp;
 use std::fmt::{self, Formatter};
+use zeroize::Zeroize;
 
 #[derive(Debug, Clone)]
 pub(crate) struct KeynumPK {
@@ -32,10 +34,19 @@ impl KeynumSK {
 
 impl fmt::Debug for KeynumSK {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        for byte in self.sk.iter() {
-            write!(f, "{:x}", byte)?
-        }
-        Ok(())
+        f.debug_struct("KeynumSK")
+            .field("keynum", &format_args!("{:X}", load_u64_le(&self.keynum)))
+            .field("sk", &format_args!("<redacted>"))
+            .field("chk", &format_args!("<redacted>"))
+            .finish()
+    }
+}
+
+impl Drop for KeynumSK {
+    fn drop(&mut self) {
+        self.keynum.zeroize();
+        self.sk.zeroize();
+        self.chk.zeroize();
     }
 }
 
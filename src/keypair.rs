@@ -1,27 +1,41 @@
 use crate::constants::*;
 use crate::crypto::ed25519;
+use crate::did::*;
 use crate::errors::*;
 use crate::helpers::*;
+use crate::kdf_params::*;
 use crate::keynum::*;
+use crate::password::*;
 use crate::public_key::*;
 use crate::secret_key::*;
+use crate::tor_client_auth::*;
 use getrandom::getrandom;
-use sha3::Digest;
-use std::io::{self, Write};
-use std::u64;
+use std::fmt;
+use std::io::Write;
 extern crate bs58;
 extern crate data_encoding;
 use data_encoding::base64url;
 use serde_json::json;
 use slip10::*;
 use std::str::FromStr;
+use zeroize::{Zeroize, Zeroizing};
 
 /// A key pair (`PublicKey` and `SecretKey`, also in esk format - expanded secret key).
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct KeyPair {
     pub pk: PublicKey,
     pub sk: SecretKey,
-    pub esk: Vec<u8>,
+    pub esk: Zeroizing<Vec<u8>>,
+}
+
+impl fmt::Debug for KeyPair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyPair")
+            .field("pk", &self.pk)
+            .field("sk", &self.sk)
+            .field("esk", &format_args!("<redacted>"))
+            .finish()
+    }
 }
 
 impl KeyPair {
@@ -35,20 +49,31 @@ impl KeyPair {
     ///
     /// You generally want to use `generated_encrypted_keypair()` instead.
     pub fn generate_unencrypted_keypair(seed: Option<Vec<u8>>) -> Result<Self> {
-        let mut seed_tmp = vec![0u8; 32];
+        Self::generate_unencrypted_keypair_with_keynum(seed, KeynumDerivation::Random)
+    }
+
+    /// Create an unencrypted key pair, choosing how the key identifier is derived.
+    ///
+    /// With a seed and `KeynumDerivation::PublicKeyHash`, the same seed
+    /// always gives the same key pair, including its identifier.
+    pub fn generate_unencrypted_keypair_with_keynum(
+        seed: Option<Vec<u8>>,
+        keynum_derivation: KeynumDerivation,
+    ) -> Result<Self> {
+        let mut seed_tmp = Zeroizing::new(vec![0u8; 32]);
         match seed {
             Some(x) => {
+                let x = Zeroizing::new(x);
                 if x.len() != 32 {
-                    return Err(PError::new(ErrorKind::Io, "Seed must be 32 bytes long"));
+                    return Err(PError::InvalidSeedLength(x.len()));
                 }
                 seed_tmp = x
             }
             None => getrandom(&mut seed_tmp)?,
         }
 
-        let (sk, pk, esk) = ed25519::keypair(&seed_tmp);
-        let mut keynum = [0u8; KEYNUM_BYTES];
-        getrandom(&mut keynum)?;
+        let (mut sk, pk, mut esk) = ed25519::keypair(&seed_tmp);
+        let keynum = keynum_derivation.keynum(&pk)?;
         let mut kdf_salt = [0u8; KDF_SALTBYTES];
         getrandom(&mut kdf_salt)?;
 
@@ -58,7 +83,7 @@ impl KeyPair {
             sig_alg: SIGALG,
             keynum_pk: KeynumPK { keynum, pk },
         };
-        let sk = SecretKey {
+        let secret_key = SecretKey {
             sig_alg: SIGALG,
             kdf_alg: KDF_ALG,
             chk_alg: CHK_ALG,
@@ -72,53 +97,66 @@ impl KeyPair {
             },
         };
 
-        Ok(KeyPair {
+        let keypair = KeyPair {
             pk,
-            sk,
-            esk: esk.to_vec(),
-        })
+            sk: secret_key,
+            esk: Zeroizing::new(esk.to_vec()),
+        };
+        sk.zeroize();
+        esk.zeroize();
+        Ok(keypair)
     }
 
     /// Create and encrypt a new key pair.
     ///
     /// If `password` is `None`, a password will be interactively asked for.
+    /// Any other `PasswordProvider` can be used instead.
     ///
     /// A key can be converted to a box in order to be serialized and saved.
     /// Ex: `pk.to_box()?.to_bytes()`
-    pub fn generate_encrypted_keypair(
+    pub fn generate_encrypted_keypair<P>(seed: Option<Vec<u8>>, password: P) -> Result<Self>
+    where
+        P: PasswordProvider,
+    {
+        Self::generate_encrypted_keypair_with_kdf(seed, password, KdfParams::default())
+    }
+
+    /// Create and encrypt a new key pair, using custom key derivation parameters.
+    ///
+    /// Cheaper parameters make the secret key faster to unlock,
+    /// more expensive ones make password guessing slower.
+    pub fn generate_encrypted_keypair_with_kdf<P>(
         seed: Option<Vec<u8>>,
-        password: Option<String>,
-    ) -> Result<Self> {
-        let KeyPair { pk, mut sk, esk } = Self::generate_unencrypted_keypair(seed)?;
+        password: P,
+        kdf_params: KdfParams,
+    ) -> Result<Self>
+    where
+        P: PasswordProvider,
+    {
+        Self::generate_encrypted_keypair_with_keynum(
+            seed,
+            password,
+            kdf_params,
+            KeynumDerivation::Random,
+        )
+    }
 
-        let interactive = password.is_none();
-        sk.write_checksum()
-            .map_err(|_| PError::new(ErrorKind::Generate, "failed to hash and write checksum!"))?;
-        let password = match password {
-            Some(password) => password,
-            None => {
-                writeln!(
-                    io::stdout(),
-                    "Please enter a password to protect the secret key."
-                )?;
-                let password = get_password("Password: ")?;
-                let password2 = get_password("Password (one more time): ")?;
-                if password != password2 {
-                    return Err(PError::new(ErrorKind::Generate, "passwords don't match!"));
-                }
-                write!(
-                    io::stdout(),
-                    "Deriving a key from the password in order to encrypt the secret key... "
-                )
-                .map_err(|e| PError::new(ErrorKind::Io, e))?;
-                io::stdout().flush()?;
-                password
-            }
-        };
-        let sk = sk.encrypt(password)?;
-        if interactive {
-            writeln!(io::stdout(), "done").map_err(|e| PError::new(ErrorKind::Io, e))?;
-        }
+    /// Create and encrypt a new key pair, using custom key derivation
+    /// parameters and choosing how the key identifier is derived.
+    pub fn generate_encrypted_keypair_with_keynum<P>(
+        seed: Option<Vec<u8>>,
+        mut password: P,
+        kdf_params: KdfParams,
+        keynum_derivation: KeynumDerivation,
+    ) -> Result<Self>
+    where
+        P: PasswordProvider,
+    {
+        let KeyPair { pk, sk, esk } =
+            Self::generate_unencrypted_keypair_with_keynum(seed, keynum_derivation)?;
+
+        let password = request_password(&mut password, PasswordPurpose::Encrypt)?;
+        let sk = sk.encrypt_with(password, kdf_params)?;
         Ok(KeyPair { pk, sk, esk })
     }
 
@@ -130,16 +168,18 @@ impl KeyPair {
     /// * `sk_writer` - Where to store the secret key box.
     /// * `comment` - An optional untrusted comment to replace the default one.
     /// * `password` - If `None`, a password will be interactively asked for.
-    pub fn generate_and_write_encrypted_keypair<W, X>(
+    ///   Any other `PasswordProvider` can be used instead.
+    pub fn generate_and_write_encrypted_keypair<W, X, P>(
         mut pk_writer: W,
         mut sk_writer: X,
         comment: Option<&str>,
-        password: Option<String>,
+        password: P,
         seed: Option<Vec<u8>>,
     ) -> Result<Self>
     where
         W: Write,
         X: Write,
+        P: PasswordProvider,
     {
         let KeyPair { pk, sk, esk } = Self::generate_encrypted_keypair(seed, password)?;
 
@@ -199,61 +239,67 @@ where
     X: Write,
     Z: Write,
 {
-    let seed = secret.keynum_sk.sk[0..32].to_vec();
-    let KeyPair { pk, sk: _, esk } = KeyPair::generate_unencrypted_keypair(Some(seed))?;
+    let pk = secret.public_key();
 
-    tor_pk_writer.write_all(b"== ed25519v1-public: type0 ==\0\0\0")?;
-    tor_pk_writer.write_all(&pk.keynum_pk.pk)?;
+    tor_pk_writer.write_all(&pk.to_tor_public_key())?;
     tor_pk_writer.flush()?;
 
     let onion_address = pk.to_onion_address();
 
-    tor_hostname_writer.write_all(&onion_address.as_bytes())?;
+    tor_hostname_writer.write_all(onion_address.to_string().as_bytes())?;
     tor_hostname_writer.flush()?;
 
-    tor_sk_writer.write_all(b"== ed25519v1-secret: type0 ==\0\0\0")?;
-    tor_sk_writer.write_all(&esk)?;
+    tor_sk_writer.write_all(&secret.to_tor_secret_key())?;
     tor_sk_writer.flush()?;
 
     Ok(true)
 }
 
+/// Write Tor client authorization keys, with the x25519 key of earlier versions.
+///
+/// Use `convert_secret_to_tor_auth_keys_with_derivation()` with
+/// `X25519Derivation::Standard` for a key that matches the onion address.
 pub fn convert_secret_to_tor_auth_keys<W, X>(
+    tor_sk_writer: W,
+    tor_pk_writer: X,
+    tor_hostname: &str,
+    secret: SecretKey,
+) -> Result<bool>
+where
+    W: Write,
+    X: Write,
+{
+    convert_secret_to_tor_auth_keys_with_derivation(
+        tor_sk_writer,
+        tor_pk_writer,
+        tor_hostname,
+        secret,
+        X25519Derivation::Legacy,
+    )
+}
+
+/// Write Tor client authorization keys, choosing how the x25519 key is derived.
+///
+/// `X25519Derivation::Standard` derives the key that a peer can compute from
+/// the onion address; `X25519Derivation::Legacy` reproduces the keys written
+/// by `convert_secret_to_tor_auth_keys()`.
+pub fn convert_secret_to_tor_auth_keys_with_derivation<W, X>(
     mut tor_sk_writer: W,
     mut tor_pk_writer: X,
     tor_hostname: &str,
     secret: SecretKey,
+    derivation: X25519Derivation,
 ) -> Result<bool>
 where
     W: Write,
     X: Write,
 {
-    let seed = secret.keynum_sk.sk[0..32].to_vec();
-    let mut seed_arr = [0u8; 32];
-    for (place, element) in seed_arr.iter_mut().zip(seed.iter()) {
-        *place = *element;
-    }
-    use x25519_dalek::{PublicKey, StaticSecret};
-    let secret = StaticSecret::from(seed_arr);
-    let public_key = PublicKey::from(&secret);
-
-    let b32_secret = base32::encode(
-        base32::Alphabet::RFC4648 { padding: false },
-        &secret.to_bytes(),
-    );
-
-    let b32_public = base32::encode(
-        base32::Alphabet::RFC4648 { padding: false },
-        public_key.as_bytes(),
-    );
-
-    tor_sk_writer.write_all(tor_hostname[0..56].as_bytes())?;
-    tor_sk_writer.write_all(b":descriptor:x25519:")?;
-    tor_sk_writer.write_all(b32_secret.as_bytes())?;
+    let auth_private = TorClientAuthPrivate::new(tor_hostname, &secret, derivation)?;
+
+    tor_sk_writer.write_all(auth_private.to_string().as_bytes())?;
     tor_sk_writer.flush()?;
 
-    tor_pk_writer.write_all(b"descriptor:x25519:")?;
-    tor_pk_writer.write_all(b32_public.as_bytes())?;
+    tor_pk_writer.write_all(auth_private.to_client_auth().to_string().as_bytes())?;
     tor_pk_writer.flush()?;
 
     Ok(true)
@@ -266,155 +312,89 @@ pub fn slip10_generate_xpriv(
     chain: &str,
 ) -> Result<Vec<u8>> {
     let seed = match secret {
-        Some(secret) => secret.keynum_sk.sk[0..32].to_vec(),
+        Some(secret) => Zeroizing::new(secret.seed()?.to_vec()),
         None => match seed_in {
-            Some(s) => s,
+            Some(s) => Zeroizing::new(s),
             None => {
                 return Err(PError::new(
-                    ErrorKind::Io,
-                    "error: Provide either seed or secret key",
+                    ErrorKind::Generate,
+                    "Provide either seed or secret key",
                 ))
             }
         },
     };
 
-    let mut seed_arr = [0u8; 32];
-    for (place, element) in seed_arr.iter_mut().zip(seed.iter()) {
-        *place = *element;
-    }
+    let chain =
+        BIP32Path::from_str(chain).map_err(|_| PError::InvalidSlip10Path(chain.to_string()))?;
+    let mut key = derive_key_from_path(&seed, Curve::Ed25519, &chain)
+        .map_err(|_| PError::Slip10Derivation)?;
+    let xpriv = key.key.to_vec();
+    key.key.zeroize();
+    key.chain_code.zeroize();
 
-    let chain = match BIP32Path::from_str(chain) {
-        Ok(ch) => ch,
-        Err(_) => {
-            return Err(PError::new(ErrorKind::Io, "error: incorrect chain"));
-        }
-    };
+    Ok(xpriv)
+}
 
-    let key = match derive_key_from_path(&seed, Curve::Ed25519, &chain) {
-        Ok(k) => k,
-        Err(_) => {
-            return Err(PError::new(ErrorKind::Io, "error: cannot derive keys"));
-        }
-    };
+/// SLIP10: derive a child key pair either from secret key or from seed.
+///
+/// The key pair is unencrypted. With `KeynumDerivation::PublicKeyHash`,
+/// deriving the same path again gives the same key identifier.
+pub fn slip10_generate_keypair(
+    secret: Option<SecretKey>,
+    seed_in: Option<Vec<u8>>,
+    chain: &str,
+    keynum_derivation: KeynumDerivation,
+) -> Result<KeyPair> {
+    let xpriv = slip10_generate_xpriv(secret, seed_in, chain)?;
+    KeyPair::generate_unencrypted_keypair_with_keynum(Some(xpriv), keynum_derivation)
+}
+
+/// Write a `did:onion` DID document, with the x25519 key agreement key of earlier versions.
+///
+/// Use `generate_did_document_with_derivation()` with
+/// `X25519Derivation::Standard` for a key agreement key that matches the DID.
+pub fn generate_did_document<W>(did_writer: W, secret: SecretKey) -> Result<bool>
+where
+    W: Write,
+{
+    generate_did_document_with_derivation(did_writer, secret, X25519Derivation::Legacy)
+}
 
-    Ok(key.key.to_vec())
+/// Write a DID document, choosing how its x25519 key agreement key is derived.
+///
+/// `X25519Derivation::Standard` derives the key that a peer can compute from
+/// the DID; `X25519Derivation::Legacy` reproduces the documents written by
+/// `generate_did_document()`.
+pub fn generate_did_document_with_derivation<W>(
+    did_writer: W,
+    secret: SecretKey,
+    derivation: X25519Derivation,
+) -> Result<bool>
+where
+    W: Write,
+{
+    write_did_document(
+        did_writer,
+        &DidDocument::onion_from_secret_key(&secret, derivation)?,
+    )
 }
 
-pub fn generate_did_document<W>(mut did_writer: W, secret: SecretKey) -> Result<bool>
+/// Write the `did:key` DID document of a key.
+///
+/// Its key agreement key is always derived with `X25519Derivation::Standard`,
+/// as `did:key` requires.
+pub fn generate_did_key_document<W>(did_writer: W, secret: SecretKey) -> Result<bool>
 where
     W: Write,
 {
-    let seed = secret.keynum_sk.sk[0..32].to_vec();
-    let KeyPair { pk, sk: _, esk: _ } = KeyPair::generate_unencrypted_keypair(Some(seed.clone()))?;
+    write_did_document(did_writer, &DidDocument::did_key(&secret.public_key())?)
+}
 
-    let pubkey_ed25519 = pk.keynum_pk.pk;
-    let mut seed_arr = [0u8; 32];
-    for (place, element) in seed_arr.iter_mut().zip(seed.iter()) {
-        *place = *element;
-    }
-    use x25519_dalek::{PublicKey, StaticSecret};
-    let secret_xd25519 = StaticSecret::from(seed_arr);
-    let pubkey_x25519 = PublicKey::from(&secret_xd25519);
-
-    // Convert pubkeys to JSON JWK format:
-    let pubkey_ed25519_jwk = base64url::encode_nopad(&pubkey_ed25519);
-    let pubkey_x25519_jwk = base64url::encode_nopad(&pubkey_x25519.to_bytes());
-
-    let pubkey_ed25519_base58 = bs58::encode(&pubkey_ed25519).into_string();
-
-    let onion = pk.to_onion_address();
-    let did_onion = format!("did:onion:{}", &onion[0..56]);
-
-    let mut did = json!({
-           "@context": ["https://www.w3.org/ns/did/v1", {"@base": did_onion} ],
-           "id" : format!("did:onion:{}", &onion[0..56]),
-           "VerificationMethod" : [
-           {
-               "id" : "TODO",
-               "type" : "JsonWebKey2020",
-               "controller" : did_onion,
-               "publicKeyJwk": {
-                  // lexicographically ordered for the purpose of digesting this obejct into id
-                  "crv": "Ed25519",
-                  "kty": "OKP",
-                  "x": pubkey_ed25519_jwk
-               }
-           },
-          {
-              "id": "TODO",
-              "type": "JsonWebKey2020",
-              "controller": did_onion,
-              "publicKeyJwk": {
-                // lexicographically ordered for the purpose of digesting this obejct into id
-                "crv": "X25519",
-                "kty": "OKP",
-                "x": pubkey_x25519_jwk
-               }
-           },
-           {
-              "id": "TODO",
-              "type": "Ed25519VerificationKey2018",
-              "controller": did_onion,
-              "publicKeyBase58": pubkey_ed25519_base58
-           }
-
-      ],
-    "authentication": [
-      "#TODO"
-    ],
-    "assertionMethod": [
-      "#TODO"
-    ],
-    "capabilityInvocation": [
-      "#TODO"
-    ],
-    "capabilityDelegation": [
-      "#TODO"
-    ],
-    "keyAgreement": [
-      "#TODO"
-    ]
-      });
-
-    // Resolve the "id": "TODO"
-    let mut pubkey_jwk_ed255 = did["VerificationMethod"][0]["publicKeyJwk"].to_string();
-    pubkey_jwk_ed255.retain(|c| !c.is_whitespace());
-    println!("{}", pubkey_jwk_ed255);
-    let mut pubkey_jwk_x255 = did["VerificationMethod"][1]["publicKeyJwk"].to_string();
-    pubkey_jwk_x255.retain(|c| !c.is_whitespace());
-    let mut pubkey_base58_ed255 = did["VerificationMethod"][2]["publicKeyBase58"].to_string();
-    pubkey_base58_ed255.retain(|c| !c.is_whitespace());
-
-    use sha2::Sha256;
-    let mut hasher = Sha256::new();
-    hasher.update(&pubkey_jwk_ed255);
-    let id_ed255 = hasher.finalize();
-    let id_ed255 = base64url::encode_nopad(&id_ed255);
-    did["VerificationMethod"][0]["id"] = json!(format!("#{}", id_ed255));
-
-    let mut hasher = Sha256::new();
-    hasher.update(&pubkey_jwk_x255);
-    let id_x255 = hasher.finalize();
-    let id_x255 = base64url::encode_nopad(&id_x255);
-    did["VerificationMethod"][1]["id"] = json!(format!("#{}", id_x255));
-
-    let mut hasher = Sha256::new();
-    hasher.update(&pubkey_base58_ed255);
-    let id_b255 = hasher.finalize();
-    let id_b255 = base64url::encode_nopad(&id_b255);
-    did["VerificationMethod"][2]["id"] = json!(format!("#{}", id_b255));
-
-    did["authentication"] = json!(format!("#{}", id_ed255));
-    // use ed25519 signature 2018 for assertion, use jws elsewhere
-    did["assertionMethod"] = json!(format!("#{}", id_b255));
-    did["capabilityInvocation"] = json!(format!("#{}", id_ed255));
-    did["capabilityDelegation"] = json!(format!("#{}", id_ed255));
-    did["keyAgreement"] = json!(format!("#{}", id_x255));
-
-    let did_doc_json = serde_json::to_string_pretty(&did).unwrap();
-    println!("{}", did_doc_json);
-
-    did_writer.write_all(did_doc_json.as_bytes())?;
+fn write_did_document<W>(mut did_writer: W, did: &DidDocument) -> Result<bool>
+where
+    W: Write,
+{
+    did_writer.write_all(did.to_string().as_bytes())?;
     did_writer.flush()?;
 
     Ok(true)
@@ -424,22 +404,15 @@ pub fn convert_secret_to_jwk<W>(mut jwk_writer: W, secret: SecretKey) -> Result<
 where
     W: Write,
 {
-    let seed = secret.keynum_sk.sk[0..32].to_vec();
-    let KeyPair { pk, sk: _, esk: _ } = KeyPair::generate_unencrypted_keypair(Some(seed.clone()))?;
+    let seed = Zeroizing::new(secret.seed()?.to_vec());
+    let mut jwk = secret.public_key().to_jwk(None);
+    jwk["d"] = json!(base64url::encode_nopad(&seed));
 
-    let pubkey_ed25519 = pk.keynum_pk.pk;
-    let mut seed_arr = [0u8; 32];
-    for (place, element) in seed_arr.iter_mut().zip(seed.iter()) {
-        *place = *element;
+    let jwk_str = Zeroizing::new(serde_json::to_string_pretty(&jwk).unwrap());
+    if let Some(serde_json::Value::String(d)) = jwk.get_mut("d") {
+        d.zeroize();
     }
-
-    // Convert pubkeys to JSON JWK format:
-    let pubkey_ed25519_jwk = base64url::encode_nopad(&pubkey_ed25519);
-    let privkey_ed25519_jwk = base64url::encode_nopad(&seed);
-
-    let jwk = json!({"kty":"OKP","crv":"Ed25519","x":pubkey_ed25519_jwk,"d":privkey_ed25519_jwk});
-
-    let jwk = serde_json::to_string_pretty(&jwk).unwrap();
+    let jwk = jwk_str;
 
     jwk_writer.write_all(jwk.as_bytes())?;
     jwk_writer.flush()?;
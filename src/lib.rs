@@ -80,20 +80,33 @@
 //!     };
 //!```
 
-#![allow(clippy::inherent_to_string)]
+#![allow(clippy::inherent_to_string, clippy::to_string_trait_impl)]
 
 mod base64;
 mod constants;
+mod credential;
 mod crypto;
+mod did;
 mod errors;
 mod helpers;
+mod jws;
+mod kdf_params;
 mod keynum;
 mod keypair;
+mod multibase;
+mod onion_address;
+mod password;
 mod public_key;
 mod secret_key;
 mod signature;
 mod signature_bones;
 mod signature_box;
+mod signature_builder;
+mod signature_verifier;
+mod tor_cert;
+mod tor_client_auth;
+mod vanity;
+mod verification_report;
 
 #[cfg(test)]
 mod tests;
@@ -106,14 +119,26 @@ use getrandom::getrandom;
 use std::io::{self, Read, Seek, SeekFrom, Write};
 
 pub use crate::constants::*;
+pub use crate::credential::*;
+pub use crate::did::*;
 pub use crate::errors::*;
+pub use crate::jws::*;
+pub use crate::kdf_params::*;
 pub use crate::keypair::*;
+pub use crate::onion_address::*;
+pub use crate::password::*;
 pub use crate::public_key::*;
 pub use crate::secret_key::*;
 pub use crate::signature_bones::*;
 pub use crate::signature_box::*;
+pub use crate::signature_builder::*;
+pub use crate::signature_verifier::*;
+pub use crate::tor_cert::*;
+pub use crate::tor_client_auth::*;
+pub use crate::vanity::*;
+pub use crate::verification_report::*;
 
-fn prehash<R>(data_reader: &mut R) -> Result<Vec<u8>>
+pub(crate) fn prehash<R>(data_reader: &mut R) -> Result<Vec<u8>>
 where
     R: Read,
 {
@@ -133,6 +158,9 @@ where
 
 /// Compute a signature.
 ///
+/// Unless `prehashed` is set, the whole input is loaded into memory.
+/// Use `sign_stream()` to sign large inputs that can be rewound.
+///
 /// # Arguments
 ///
 /// * `pk` - an optional public key. If provided, it must be the public key from the original key pair.
@@ -159,6 +187,69 @@ where
         data_reader.read_to_end(&mut data)?;
         data
     };
+    let mut z = vec![0; 64];
+    getrandom(&mut z)?;
+    let signature_raw = sk.sign_raw(&data, Some(&z));
+    signature_box_from_raw(
+        pk,
+        sk,
+        signature_raw,
+        prehashed,
+        trusted_comment,
+        untrusted_comment,
+    )
+}
+
+/// Compute a signature, reading the data in bounded memory.
+///
+/// Non-prehashed signatures require two passes over the data, so the reader
+/// is rewound to its initial position once the first pass is complete.
+///
+/// # Arguments
+///
+/// * `pk` - an optional public key. If provided, it must be the public key from the original key pair.
+/// * `sk` - the secret key
+/// * `data_reader` - the source of the data to be signed
+/// * `prehashed` - use prehashing
+/// * `trusted_comment` - overrides the default trusted comment
+/// * `untrusted_comment` - overrides the default untrusted comment
+pub fn sign_stream<R>(
+    pk: Option<&PublicKey>,
+    sk: &SecretKey,
+    mut data_reader: R,
+    prehashed: bool,
+    trusted_comment: Option<&str>,
+    untrusted_comment: Option<&str>,
+) -> Result<SignatureBox>
+where
+    R: Read + Seek,
+{
+    let mut z = vec![0; 64];
+    getrandom(&mut z)?;
+    let signature_raw = if prehashed {
+        let data = prehash(&mut data_reader)?;
+        sk.sign_raw(&data, Some(&z))
+    } else {
+        sk.sign_reader(&mut data_reader, Some(&z))?
+    };
+    signature_box_from_raw(
+        pk,
+        sk,
+        signature_raw,
+        prehashed,
+        trusted_comment,
+        untrusted_comment,
+    )
+}
+
+pub(crate) fn signature_box_from_raw(
+    pk: Option<&PublicKey>,
+    sk: &SecretKey,
+    signature_raw: [u8; SIGNATURE_BYTES],
+    prehashed: bool,
+    trusted_comment: Option<&str>,
+    untrusted_comment: Option<&str>,
+) -> Result<SignatureBox> {
     let trusted_comment = match trusted_comment {
         Some(trusted_comment) => trusted_comment.to_string(),
         None => format!("timestamp:{}", unix_timestamp()),
@@ -168,33 +259,27 @@ where
         None => DEFAULT_COMMENT.to_string(),
     };
     let mut signature = Signature::default();
+    // expanded secret keys make standard Ed25519 signatures
     if !prehashed {
-        signature.sig_alg = sk.sig_alg;
+        signature.sig_alg = SIGALG;
     } else {
         signature.sig_alg = SIGALG_PREHASHED;
     }
     signature.keynum.copy_from_slice(&sk.keynum_sk.keynum[..]);
-    let mut z = vec![0; 64];
-    getrandom(&mut z)?;
-    let signature_raw = ed25519::signature(&data, &sk.keynum_sk.sk, Some(&z));
     signature.sig.copy_from_slice(&signature_raw[..]);
 
     let mut sig_and_trusted_comment: Vec<u8> = vec![];
     sig_and_trusted_comment.extend(signature.sig.iter());
     sig_and_trusted_comment.extend(trusted_comment.as_bytes().iter());
 
+    let mut z = vec![0; 64];
     getrandom(&mut z)?;
-    let global_sig = ed25519::signature(&sig_and_trusted_comment, &sk.keynum_sk.sk, Some(&z));
+    let global_sig = sk.sign_raw(&sig_and_trusted_comment, Some(&z));
     if let Some(pk) = pk {
         if !ed25519::verify(&sig_and_trusted_comment, &pk.keynum_pk.pk[..], &global_sig) {
-            return Err(PError::new(
-                ErrorKind::Verify,
-                format!(
-                    "Could not verify signature with the \
-                     provided public key ID: {:X}",
-                    load_u64_le(&pk.keynum_pk.keynum[..])
-                ),
-            ));
+            return Err(PError::PublicKeyMismatch {
+                pk: load_u64_le(&pk.keynum_pk.keynum[..]),
+            });
         }
     }
     let signature_box = SignatureBox {
@@ -207,8 +292,84 @@ where
     Ok(signature_box)
 }
 
+/// Verify a signature using a public key, and describe what was verified.
+///
+/// This function has no side effects besides reading the data and, if
+/// requested, copying it to `output` once the signature has been verified.
+///
+/// # Arguments
+///
+/// * `pk` - the public key
+/// * `signature_box` - the signature and its metadata
+/// * `data_reader` - the data source
+/// * `output` - if provided, receives a copy of the verified data
+pub fn verify_report<R>(
+    pk: &PublicKey,
+    signature_box: &SignatureBox,
+    mut data_reader: R,
+    output: Option<&mut dyn Write>,
+) -> Result<VerificationReport>
+where
+    R: Read + Seek,
+{
+    let start = data_reader.stream_position()?;
+    verify_keynum(pk, signature_box)?;
+    let sig = &signature_box.signature;
+    let valid = if signature_box.is_prehashed() {
+        let data = prehash(&mut data_reader)?;
+        ed25519::verify(&data, &pk.keynum_pk.pk, &sig.sig)
+    } else {
+        ed25519::verify_reader(&mut data_reader, &pk.keynum_pk.pk, &sig.sig)?
+    };
+    if !valid {
+        return Err(PError::SignatureInvalid);
+    }
+    verify_trusted_comment(pk, signature_box)?;
+    let report = VerificationReport::new(signature_box)?;
+    if let Some(output) = output {
+        data_reader.seek(SeekFrom::Start(start))?;
+        copy_data(&mut data_reader, output)?;
+    }
+    Ok(report)
+}
+
+/// Verify a signature using the onion address of the signer as its identity,
+/// and describe what was verified.
+///
+/// A v3 onion address is the public key of the service, but not its key
+/// identifier. The key identifier of a signature is not covered by the
+/// signature itself, so it is not checked, and the report doesn't include
+/// it: only the key matters.
+///
+/// # Arguments
+///
+/// * `onion_address` - the onion address of the signer
+/// * `signature_box` - the signature and its metadata
+/// * `data_reader` - the data source
+/// * `output` - if provided, receives a copy of the verified data
+pub fn verify_onion<R>(
+    onion_address: &OnionAddress,
+    signature_box: &SignatureBox,
+    data_reader: R,
+    output: Option<&mut dyn Write>,
+) -> Result<VerificationReport>
+where
+    R: Read + Seek,
+{
+    let mut pk = onion_address.public_key();
+    pk.keynum_pk.keynum = signature_box.get_keynum();
+    let mut report = verify_report(&pk, signature_box, data_reader, output)?;
+    report.keynum = None;
+    Ok(report)
+}
+
 /// Verify a signature using a public key.
 ///
+/// The data is read in bounded memory, whether it was prehashed or not.
+///
+/// Applications that shouldn't write to the standard streams should use
+/// `verify_report()` instead.
+///
 /// # Arguments
 ///
 /// * `pk` - the public key
@@ -226,67 +387,60 @@ pub fn verify<R>(
 where
     R: Read + Seek,
 {
-    let data = if signature_box.is_prehashed() {
-        prehash(&mut data_reader)?
-    } else {
-        let mut data = vec![];
-        data_reader.read_to_end(&mut data)?;
-        data
-    };
+    let start = data_reader.stream_position()?;
+    let report = verify_report(pk, signature_box, &mut data_reader, None)?;
+    if !quiet {
+        eprintln!("Signature and comment signature verified");
+        if let Some(trusted_comment) = report.trusted_comment() {
+            eprintln!("Trusted comment: {}", trusted_comment);
+        }
+    }
+    if output {
+        data_reader.seek(SeekFrom::Start(start))?;
+        copy_data(&mut data_reader, &mut io::stdout())?;
+    }
+    Ok(())
+}
+
+fn copy_data<R>(data_reader: &mut R, output: &mut dyn Write) -> Result<()>
+where
+    R: Read,
+{
+    let mut buf = vec![0; 65536];
+    loop {
+        let len = data_reader.read(&mut buf)?;
+        if len == 0 {
+            break;
+        }
+        output.write_all(&buf[..len])?;
+    }
+    output.flush()?;
+    Ok(())
+}
+
+pub(crate) fn verify_keynum(pk: &PublicKey, signature_box: &SignatureBox) -> Result<()> {
     let sig = &signature_box.signature;
     if sig.keynum != pk.keynum_pk.keynum {
-        return Err(PError::new(
-            ErrorKind::Verify,
-            format!(
-                "Signature key id: {:X} is different from public key: {:X}",
-                load_u64_le(&sig.keynum[..]),
-                load_u64_le(&pk.keynum_pk.keynum[..])
-            ),
-        ));
-    }
-    if !ed25519::verify(&data, &pk.keynum_pk.pk, &sig.sig) {
-        return Err(PError::new(
-            ErrorKind::Verify,
-            "Signature verification failed",
-        ));
+        return Err(PError::KeyIdMismatch {
+            sig: load_u64_le(&sig.keynum[..]),
+            pk: load_u64_le(&pk.keynum_pk.keynum[..]),
+        });
     }
+    Ok(())
+}
+
+pub(crate) fn verify_trusted_comment(pk: &PublicKey, signature_box: &SignatureBox) -> Result<()> {
     match (
         &signature_box.sig_and_trusted_comment,
         &signature_box.global_sig,
     ) {
         (Some(sig_and_trusted_comment), Some(global_sig)) => {
-            if !ed25519::verify(&sig_and_trusted_comment, &pk.keynum_pk.pk, &global_sig[..]) {
-                return Err(PError::new(
-                    ErrorKind::Verify,
-                    "Comment signature verification failed",
-                ));
+            if !ed25519::verify(sig_and_trusted_comment, &pk.keynum_pk.pk, &global_sig[..]) {
+                return Err(PError::TrustedCommentSignatureInvalid);
             }
         }
         (None, None) => {}
-        _ => {
-            return Err(PError::new(
-                ErrorKind::Verify,
-                "Inconsistent signature presence for trusted comment presence",
-            ))
-        }
+        _ => return Err(PError::TrustedCommentMissing),
     };
-    if !quiet {
-        eprintln!("Signature and comment signature verified");
-        if signature_box.global_sig.is_some() {
-            eprintln!("Trusted comment: {}", signature_box.trusted_comment()?);
-        }
-    }
-    if output {
-        data_reader.seek(SeekFrom::Start(0))?;
-        let mut buf = vec![0; 65536];
-        loop {
-            let len = data_reader.read(&mut buf)?;
-            if len == 0 {
-                break;
-            }
-            io::stdout().write_all(&buf[..len])?;
-        }
-        io::stdout().flush()?;
-    }
     Ok(())
 }
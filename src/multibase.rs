@@ -0,0 +1,18 @@
+pub(crate) const MULTICODEC_ED25519_PUB: [u8; 2] = [0xed, 0x01];
+pub(crate) const MULTICODEC_X25519_PUB: [u8; 2] = [0xec, 0x01];
+
+/// Encode a key as base58btc multibase, prefixed with its multicodec.
+pub(crate) fn multibase_encode(codec: [u8; 2], key: &[u8]) -> String {
+    let mut bin = codec.to_vec();
+    bin.extend_from_slice(key);
+    format!("z{}", bs58::encode(bin).into_string())
+}
+
+/// Decode a base58btc multibase key into its multicodec and the key itself.
+pub(crate) fn multibase_decode(multibase: &str) -> Option<([u8; 2], Vec<u8>)> {
+    let bin = bs58::decode(multibase.strip_prefix('z')?).into_vec().ok()?;
+    if bin.len() < 2 {
+        return None;
+    }
+    Some(([bin[0], bin[1]], bin[2..].to_vec()))
+}
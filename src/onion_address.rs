@@ -0,0 +1,116 @@
+use crate::constants::*;
+use crate::crypto::ed25519;
+use crate::errors::*;
+use crate::keynum::*;
+use crate::public_key::*;
+use base32::Alphabet;
+use sha3::{Digest, Sha3_256};
+use std::fmt;
+use std::str::FromStr;
+
+const ONION_VERSION: u8 = 3;
+const ONION_SUFFIX: &str = ".onion";
+const ONION_ADDR_LEN: usize = 56;
+
+/// A v3 onion service address (rend-spec-v3, section 6).
+///
+/// `base32(PUBKEY | CHECKSUM | VERSION) + ".onion"`, with
+/// `CHECKSUM = H(".onion checksum" | PUBKEY | VERSION)[:2]`.
+///
+/// Parsing accepts either case, an optional `.onion` suffix and subdomains
+/// such as `www.<addr>.onion`; the address is always displayed in its
+/// canonical, lowercase form with the `.onion` suffix.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct OnionAddress {
+    pk: [u8; PUBLICKEY_BYTES],
+}
+
+fn onion_checksum(pk: &[u8; PUBLICKEY_BYTES]) -> [u8; 2] {
+    let mut hasher = Sha3_256::new();
+    hasher.update(b".onion checksum");
+    hasher.update(pk);
+    hasher.update([ONION_VERSION]);
+    let dgst = hasher.finalize();
+    [dgst[0], dgst[1]]
+}
+
+impl OnionAddress {
+    /// The onion address of the service whose identity key is `pk`.
+    pub fn from_public_key(pk: &PublicKey) -> OnionAddress {
+        OnionAddress {
+            pk: pk.keynum_pk.pk,
+        }
+    }
+
+    /// The identity key of the onion service.
+    ///
+    /// An onion address doesn't include a key identifier, so the returned key
+    /// has an all-zero one; use `PublicKey::from_onion_address` to set it.
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey {
+            sig_alg: SIGALG,
+            keynum_pk: KeynumPK {
+                keynum: [0u8; KEYNUM_BYTES],
+                pk: self.pk,
+            },
+        }
+    }
+
+    /// The two-byte checksum encoded in the address.
+    pub fn checksum(&self) -> [u8; 2] {
+        onion_checksum(&self.pk)
+    }
+
+    /// The version encoded in the address; always 3.
+    pub fn version(&self) -> u8 {
+        ONION_VERSION
+    }
+
+    /// The 56-character address, without the `.onion` suffix.
+    pub fn without_suffix(&self) -> String {
+        let mut bin = [0u8; PUBLICKEY_BYTES + 3];
+        bin[..PUBLICKEY_BYTES].copy_from_slice(&self.pk);
+        bin[PUBLICKEY_BYTES..PUBLICKEY_BYTES + 2].copy_from_slice(&self.checksum());
+        bin[PUBLICKEY_BYTES + 2] = ONION_VERSION;
+        base32::encode(Alphabet::RFC4648 { padding: false }, &bin).to_ascii_lowercase()
+    }
+}
+
+impl FromStr for OnionAddress {
+    type Err = PError;
+
+    fn from_str(s: &str) -> Result<OnionAddress> {
+        if !s.is_ascii() {
+            return Err(PError::InvalidOnionEncoding);
+        }
+        let hostname = s.trim().trim_end_matches('.').to_ascii_lowercase();
+        let hostname = hostname.strip_suffix(ONION_SUFFIX).unwrap_or(&hostname);
+        // only the rightmost label is the address, the rest are subdomains
+        let label = hostname.rsplit('.').next().unwrap_or(hostname);
+        if label.len() != ONION_ADDR_LEN {
+            return Err(PError::InvalidOnionLength(label.len()));
+        }
+        let decoded = base32::decode(Alphabet::RFC4648 { padding: false }, label)
+            .filter(|decoded| decoded.len() == PUBLICKEY_BYTES + 3)
+            .ok_or(PError::InvalidOnionEncoding)?;
+        let version = decoded[PUBLICKEY_BYTES + 2];
+        if version != ONION_VERSION {
+            return Err(PError::InvalidOnionVersion(version));
+        }
+        let mut pk = [0u8; PUBLICKEY_BYTES];
+        pk.copy_from_slice(&decoded[..PUBLICKEY_BYTES]);
+        if onion_checksum(&pk) != decoded[PUBLICKEY_BYTES..PUBLICKEY_BYTES + 2] {
+            return Err(PError::InvalidOnionChecksum);
+        }
+        if !ed25519::is_valid_public_key(&pk) {
+            return Err(PError::InvalidPublicKey);
+        }
+        Ok(OnionAddress { pk })
+    }
+}
+
+impl fmt::Display for OnionAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.without_suffix(), ONION_SUFFIX)
+    }
+}
@@ -0,0 +1,264 @@
+use crate::constants::*;
+use crate::errors::*;
+use crate::helpers::*;
+use std::env;
+use std::fmt::{self, Formatter};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use zeroize::Zeroizing;
+
+/// A password, wiped from memory when dropped.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Password(Zeroizing<String>);
+
+impl Password {
+    /// The password, as bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+
+    /// The password, as a string.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The length of the password, in bytes.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if the password is empty.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Default for Password {
+    fn default() -> Self {
+        Password::from(String::new())
+    }
+}
+
+impl From<String> for Password {
+    fn from(password: String) -> Password {
+        Password(Zeroizing::new(password))
+    }
+}
+
+impl From<&str> for Password {
+    fn from(password: &str) -> Password {
+        Password::from(password.to_string())
+    }
+}
+
+impl fmt::Debug for Password {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Password(<redacted>)")
+    }
+}
+
+/// The reason why a password is being requested.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PasswordPurpose {
+    /// The password protecting an existing secret key.
+    Decrypt,
+    /// A new password, used to encrypt a secret key.
+    Encrypt,
+}
+
+/// A source of passwords for encrypting and decrypting secret keys.
+///
+/// Every function that needs a password accepts a `PasswordProvider`.
+/// A `Password` can be given directly. `Option<String>` implements it as well,
+/// so that `None` falls back to an interactive prompt, but the caller's copy
+/// of a `String` password is not wiped from memory.
+pub trait PasswordProvider {
+    /// Return a password for the given purpose.
+    fn password(&mut self, purpose: PasswordPurpose) -> Result<Password>;
+}
+
+impl<P: PasswordProvider + ?Sized> PasswordProvider for &mut P {
+    fn password(&mut self, purpose: PasswordPurpose) -> Result<Password> {
+        (**self).password(purpose)
+    }
+}
+
+impl PasswordProvider for Password {
+    fn password(&mut self, _purpose: PasswordPurpose) -> Result<Password> {
+        Ok(self.clone())
+    }
+}
+
+impl PasswordProvider for String {
+    fn password(&mut self, _purpose: PasswordPurpose) -> Result<Password> {
+        Ok(Password::from(self.as_str()))
+    }
+}
+
+impl PasswordProvider for Option<String> {
+    fn password(&mut self, purpose: PasswordPurpose) -> Result<Password> {
+        match self {
+            Some(password) => Ok(Password::from(password.as_str())),
+            None => TtyPasswordProvider.password(purpose),
+        }
+    }
+}
+
+/// Prompt for passwords on the terminal.
+///
+/// New passwords have to be typed twice. Prompts and progress messages are
+/// written to stderr, so that they don't end up in piped output.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TtyPasswordProvider;
+
+impl PasswordProvider for TtyPasswordProvider {
+    fn password(&mut self, purpose: PasswordPurpose) -> Result<Password> {
+        match purpose {
+            PasswordPurpose::Decrypt => {
+                let password = get_password("Password: ")?;
+                writeln!(
+                    io::stderr(),
+                    "Deriving a key from the password and decrypting the secret key..."
+                )?;
+                Ok(password)
+            }
+            PasswordPurpose::Encrypt => {
+                writeln!(
+                    io::stderr(),
+                    "Please enter a password to protect the secret key."
+                )?;
+                let password = get_password("Password: ")?;
+                let password2 = get_password("Password (one more time): ")?;
+                if password != password2 {
+                    return Err(PError::PasswordMismatch);
+                }
+                writeln!(
+                    io::stderr(),
+                    "Deriving a key from the password in order to encrypt the secret key..."
+                )?;
+                Ok(password)
+            }
+        }
+    }
+}
+
+/// Read the password from an environment variable.
+#[derive(Clone, Debug)]
+pub struct EnvPasswordProvider {
+    var: String,
+}
+
+impl EnvPasswordProvider {
+    /// Use the content of the environment variable `var` as the password.
+    pub fn new(var: &str) -> EnvPasswordProvider {
+        EnvPasswordProvider {
+            var: var.to_string(),
+        }
+    }
+}
+
+impl PasswordProvider for EnvPasswordProvider {
+    fn password(&mut self, _purpose: PasswordPurpose) -> Result<Password> {
+        env::var(&self.var)
+            .map(Password::from)
+            .map_err(|e| PError::PasswordUnavailable(format!("${}: {}", self.var, e)))
+    }
+}
+
+/// Read passwords from a reader, one line per password.
+///
+/// This is typically used with a pipe or a file descriptor inherited from
+/// a parent process.
+#[derive(Debug)]
+pub struct ReaderPasswordProvider<R: Read> {
+    reader: BufReader<R>,
+}
+
+impl<R: Read> ReaderPasswordProvider<R> {
+    /// Read passwords from `reader`.
+    pub fn new(reader: R) -> ReaderPasswordProvider<R> {
+        ReaderPasswordProvider {
+            reader: BufReader::new(reader),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl ReaderPasswordProvider<std::fs::File> {
+    /// Read passwords from the file descriptor `fd`.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be an open file descriptor, owned by the returned provider.
+    pub unsafe fn from_raw_fd(fd: std::os::unix::io::RawFd) -> Self {
+        use std::os::unix::io::FromRawFd;
+        ReaderPasswordProvider::new(std::fs::File::from_raw_fd(fd))
+    }
+}
+
+impl<R: Read> PasswordProvider for ReaderPasswordProvider<R> {
+    fn password(&mut self, _purpose: PasswordPurpose) -> Result<Password> {
+        let mut line = Zeroizing::new(String::new());
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(PError::PasswordUnavailable(
+                "no password to read".to_string(),
+            ));
+        }
+        Ok(Password::from(line.trim_end_matches(&['\r', '\n'][..])))
+    }
+}
+
+/// Ask a closure for passwords.
+pub struct CallbackPasswordProvider<F>(pub F)
+where
+    F: FnMut(PasswordPurpose) -> Result<Password>;
+
+impl<F> PasswordProvider for CallbackPasswordProvider<F>
+where
+    F: FnMut(PasswordPurpose) -> Result<Password>,
+{
+    fn password(&mut self, purpose: PasswordPurpose) -> Result<Password> {
+        (self.0)(purpose)
+    }
+}
+
+/// Always return the same password, and count how many times it was requested.
+///
+/// Mostly useful for tests.
+#[derive(Clone, Debug, Default)]
+pub struct StaticPasswordProvider {
+    password: Password,
+    requests: usize,
+}
+
+impl StaticPasswordProvider {
+    /// Always return `password`.
+    pub fn new(password: &str) -> StaticPasswordProvider {
+        StaticPasswordProvider {
+            password: Password::from(password),
+            requests: 0,
+        }
+    }
+
+    /// The number of times a password was requested.
+    pub fn requests(&self) -> usize {
+        self.requests
+    }
+}
+
+impl PasswordProvider for StaticPasswordProvider {
+    fn password(&mut self, _purpose: PasswordPurpose) -> Result<Password> {
+        self.requests += 1;
+        Ok(self.password.clone())
+    }
+}
+
+pub(crate) fn request_password<P>(provider: &mut P, purpose: PasswordPurpose) -> Result<Password>
+where
+    P: PasswordProvider + ?Sized,
+{
+    let password = provider.password(purpose)?;
+    if password.len() > PASSWORD_MAXBYTES {
+        return Err(PError::PasswordTooLong);
+    }
+    Ok(password)
+}
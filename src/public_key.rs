@@ -1,10 +1,17 @@
 use crate::base64::{Base64, Decoder, Encoder};
 use crate::constants::*;
+use crate::crypto::blake2b::Blake2b;
+use crate::crypto::ed25519;
 use crate::crypto::util::fixed_time_eq;
 use crate::errors::*;
 use crate::helpers::*;
 use crate::keynum::*;
-use base32::{decode, Alphabet};
+use crate::multibase::*;
+use crate::onion_address::*;
+use data_encoding::base64url;
+use getrandom::getrandom;
+use serde_json::{json, Value};
+use sha2::Sha256;
 use sha3::{Digest, Sha3_256};
 use std::cmp;
 use std::fmt::Write as fmtWrite;
@@ -23,15 +30,15 @@ use std::path::Path;
 #[derive(Clone, Debug)]
 pub struct PublicKeyBox(String);
 
-impl Into<String> for PublicKeyBox {
-    fn into(self) -> String {
-        self.0
+impl From<PublicKeyBox> for String {
+    fn from(val: PublicKeyBox) -> Self {
+        val.0
     }
 }
 
-impl Into<PublicKeyBox> for String {
-    fn into(self) -> PublicKeyBox {
-        PublicKeyBox(self)
+impl From<String> for PublicKeyBox {
+    fn from(val: String) -> Self {
+        PublicKeyBox(val)
     }
 }
 
@@ -41,9 +48,9 @@ impl ToString for PublicKeyBox {
     }
 }
 
-impl Into<PublicKey> for PublicKeyBox {
-    fn into(self) -> PublicKey {
-        self.into_public_key().unwrap()
+impl From<PublicKeyBox> for PublicKey {
+    fn from(val: PublicKeyBox) -> Self {
+        val.into_public_key().unwrap()
     }
 }
 
@@ -69,6 +76,44 @@ impl PublicKeyBox {
     }
 }
 
+/// How the key identifier of a new key is chosen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeynumDerivation {
+    /// A random identifier, as chosen by the reference implementation.
+    #[default]
+    Random,
+    /// The 8-byte BLAKE2b hash of the public key.
+    ///
+    /// The same key always gets the same identifier, so it can be
+    /// recovered from a seed or from an onion address.
+    PublicKeyHash,
+}
+
+/// Decode the base64url-encoded `name` member of an Ed25519 OKP JSON Web Key.
+pub(crate) fn ed25519_jwk_member(jwk: &Value, name: &'static str) -> Result<Option<Vec<u8>>> {
+    if jwk.get("kty") != Some(&json!("OKP")) || jwk.get("crv") != Some(&json!("Ed25519")) {
+        return Err(PError::InvalidJwk("not an Ed25519 OKP key"));
+    }
+    match jwk.get(name) {
+        Some(Value::String(member)) => base64url::decode_nopad(member.as_bytes())
+            .map(Some)
+            .map_err(|_| PError::InvalidJwk("invalid base64url encoding")),
+        Some(_) => Err(PError::InvalidJwk("key members must be strings")),
+        None => Ok(None),
+    }
+}
+
+impl KeynumDerivation {
+    pub(crate) fn keynum(self, pk: &[u8; PUBLICKEY_BYTES]) -> Result<[u8; KEYNUM_BYTES]> {
+        let mut keynum = [0u8; KEYNUM_BYTES];
+        match self {
+            KeynumDerivation::Random => getrandom(&mut keynum)?,
+            KeynumDerivation::PublicKeyHash => Blake2b::blake2b(&mut keynum, pk),
+        }
+        Ok(keynum)
+    }
+}
+
 /// A `PublicKey` is used to verify signatures.
 #[derive(Clone, Debug)]
 pub struct PublicKey {
@@ -82,6 +127,15 @@ impl PublicKey {
         &self.keynum_pk.keynum[..]
     }
 
+    /// Replace the key identifier with the one derived from the key itself.
+    ///
+    /// This recovers the identifier of keys created with
+    /// `KeynumDerivation::PublicKeyHash`, e.g. from an onion address.
+    pub fn with_derived_keynum(mut self) -> PublicKey {
+        Blake2b::blake2b(&mut self.keynum_pk.keynum, &self.keynum_pk.pk);
+        self
+    }
+
     /// Deserialize a `PublicKey`.
     ///
     /// For storage, a `PublicKeyBox` is usually what you need instead.
@@ -103,10 +157,11 @@ impl PublicKey {
     ///
     /// For storage, a `PublicKeyBox` is usually what you want to use instead.
     pub fn to_bytes(&self) -> Vec<u8> {
-        let mut iters = Vec::new();
-        iters.push(self.sig_alg.iter());
-        iters.push(self.keynum_pk.keynum.iter());
-        iters.push(self.keynum_pk.pk.iter());
+        let iters = [
+            self.sig_alg.iter(),
+            self.keynum_pk.keynum.iter(),
+            self.keynum_pk.pk.iter(),
+        ];
         let v: Vec<u8> = iters
             .iter()
             .flat_map(|b| {
@@ -121,31 +176,21 @@ impl PublicKey {
     pub fn from_box(pk_box: PublicKeyBox) -> Result<PublicKey> {
         let s = pk_box.0;
         let mut lines = s.lines();
-        lines.next().ok_or_else(|| {
-            PError::new(ErrorKind::Io, "Missing comment in public key".to_string())
-        })?;
-        let encoded_pk = lines.next().ok_or_else(|| {
-            PError::new(
-                ErrorKind::Io,
-                "Missing encoded key in public key".to_string(),
-            )
-        })?;
+        lines
+            .next()
+            .ok_or(PError::MissingField("comment in public key"))?;
+        let encoded_pk = lines
+            .next()
+            .ok_or(PError::MissingField("encoded key in public key"))?;
         if encoded_pk.len() != PK_B64_ENCODED_LEN {
-            return Err(PError::new(
-                ErrorKind::Io,
-                "Base64 conversion failed - was an actual public key given?".to_string(),
-            ));
+            return Err(PError::InvalidBase64Length {
+                expected: PK_B64_ENCODED_LEN,
+                got: encoded_pk.len(),
+            });
         }
-        let decoded_buf = Base64::decode_to_vec(encoded_pk.trim()).map_err(|e| {
-            PError::new(
-                ErrorKind::Io,
-                format!(
-                    "Base64 conversion failed - was an actual public key given?: {}",
-                    e
-                ),
-            )
-        })?;
-        Ok(PublicKey::from_bytes(&decoded_buf)?)
+        let decoded_buf = Base64::decode_to_vec(encoded_pk.trim())
+            .map_err(|_| PError::InvalidBase64("public key"))?;
+        PublicKey::from_bytes(&decoded_buf)
     }
 
     /// Convert a `PublicKey` to a `PublicKeyBox`.
@@ -161,20 +206,13 @@ impl PublicKey {
     pub fn from_base64(pk_string: &str) -> Result<PublicKey> {
         let encoded_string = pk_string.to_string();
         if encoded_string.trim().len() != PK_B64_ENCODED_LEN {
-            return Err(PError::new(
-                ErrorKind::Io,
-                "base64 conversion failed - was an actual public key given?".to_string(),
-            ));
+            return Err(PError::InvalidBase64Length {
+                expected: PK_B64_ENCODED_LEN,
+                got: encoded_string.trim().len(),
+            });
         }
-        let decoded_string = Base64::decode_to_vec(encoded_string.as_bytes()).map_err(|e| {
-            PError::new(
-                ErrorKind::Io,
-                format!(
-                    "base64 conversion failed - was an actual public key given?: {}",
-                    e
-                ),
-            )
-        })?;
+        let decoded_string = Base64::decode_to_vec(encoded_string.as_bytes())
+            .map_err(|_| PError::InvalidBase64("public key"))?;
         PublicKey::from_bytes(&decoded_string)
     }
 
@@ -192,93 +230,186 @@ impl PublicKey {
         PublicKey::from_box(s.into())
     }
 
-    pub fn to_onion_address(&self) -> String {
-        // convert to onion address/hostname
-        // onion_address = base32(PUBKEY | CHECKSUM | VERSION) + ".onion"
-        //encode(Alphabet::RFC4648 { padding: false }, &onion_addr[0..56]).unwrap();
-        //".onion checksum"
-        // CHECKSUM = H(".onion checksum" | PUBKEY | VERSION)[:2]
-        let mut hasher = Sha3_256::new();
-        hasher.update(b".onion checksum");
-        hasher.update(self.keynum_pk.pk);
-        hasher.update([3]); // version
-        let dgst = hasher.finalize();
-        let mut chk = [0u8; 2];
-        chk.copy_from_slice(&dgst[0..2]);
-
-        let mut addr: Vec<u8> = self.keynum_pk.pk.clone().into();
-        addr.extend(chk.iter().copied());
-        addr.push(3); // version
-
-        let mut arr = [0u8; 35];
-        for (place, element) in arr.iter_mut().zip(addr.iter()) {
-            *place = *element;
+    /// Create a `PublicKey` from the content of a Tor `hs_ed25519_public_key` file.
+    ///
+    /// Tor keys have no key identifier, so `keynum` has to be provided.
+    pub fn from_tor_public_key(bytes: &[u8], keynum: [u8; KEYNUM_BYTES]) -> Result<PublicKey> {
+        if bytes.len() != TOR_PUBLICKEY_HEADER.len() + PUBLICKEY_BYTES
+            || !bytes.starts_with(TOR_PUBLICKEY_HEADER)
+        {
+            return Err(PError::InvalidTorKey);
         }
+        let mut pk = [0u8; PUBLICKEY_BYTES];
+        pk.copy_from_slice(&bytes[TOR_PUBLICKEY_HEADER.len()..]);
+        Ok(PublicKey {
+            sig_alg: SIGALG,
+            keynum_pk: KeynumPK { keynum, pk },
+        })
+    }
 
-        base32::encode(Alphabet::RFC4648 { padding: false }, &arr).to_ascii_lowercase() + ".onion"
+    /// Serialize a `PublicKey` as a Tor `hs_ed25519_public_key` file.
+    pub fn to_tor_public_key(&self) -> Vec<u8> {
+        let mut v = TOR_PUBLICKEY_HEADER.to_vec();
+        v.extend_from_slice(&self.keynum_pk.pk);
+        v
     }
 
-    pub fn from_onion_address(
-        onion_addr: &str,
-        sig_alg: [u8; TWOBYTES],
-        keynum: [u8; KEYNUM_BYTES],
-    ) -> Result<PublicKey> {
-        // onion address is 56 + 6 characters long, e.g. fscst5exmlmr262byztwz4kzhggjlzumvc2ndvgytzoucr2tkgxf7mid.onion
-        if onion_addr.len() != (56 + 6) {
-            return Err(PError::new(
-                ErrorKind::Io,
-                "error: onion address length".to_string(),
-            ));
-        }
+    /// Convert this key to the X25519 public key used for key agreement.
+    ///
+    /// This is the birational map from Edwards to Montgomery form, so it
+    /// matches `SecretKey::to_x25519(X25519Derivation::Standard)`.
+    pub fn to_x25519(&self) -> Result<[u8; 32]> {
+        ed25519::x25519_public_key(&self.keynum_pk.pk).ok_or(PError::InvalidPublicKey)
+    }
 
-        let mut pk = [0u8; PUBLICKEY_BYTES];
-        // onion_address = base32(PUBKEY | CHECKSUM | VERSION) + ".onion"
-        // CHECKSUM = H(".onion checksum" | PUBKEY | VERSION)[:2]
-        let onion_decoded = decode(Alphabet::RFC4648 { padding: false }, &onion_addr[0..56]);
-
-        let onion_decoded = match onion_decoded {
-            Some(onion) => onion,
-            None => {
-                return Err(PError::new(
-                    ErrorKind::Io,
-                    "error: cannot decode onion address".to_string(),
-                ));
-            }
-        };
-
-        let mut hasher = Sha3_256::new();
-        hasher.update(b".onion checksum");
-        hasher.update(&onion_decoded[0..32]);
-        hasher.update([3]); // version
-        let dgst = hasher.finalize();
-        let mut chk_calc = [0u8; 2];
-        chk_calc.copy_from_slice(&dgst[0..2]);
-
-        if onion_decoded[34] != 3 {
-            return Err(PError::new(
-                ErrorKind::Io,
-                "Onion version incorrect".to_string(),
-            ));
+    /// Derive the blinded key of an onion service identity key for a time period.
+    ///
+    /// This is the key clients use to fetch the descriptor of the service
+    /// (rend-spec-v3, appendix A.2). `period_length` is in minutes, usually
+    /// `ONION_TIME_PERIOD_LENGTH`. The key identifier is kept as is.
+    pub fn blind(&self, time_period: u64, period_length: u64) -> Result<PublicKey> {
+        let param = onion_blinding_param(&self.keynum_pk.pk, time_period, period_length)?;
+        let pk = ed25519::blind_public_key(&self.keynum_pk.pk, &param)
+            .ok_or(PError::InvalidPublicKey)?;
+        Ok(PublicKey {
+            sig_alg: self.sig_alg,
+            keynum_pk: KeynumPK {
+                keynum: self.keynum_pk.keynum,
+                pk,
+            },
+        })
+    }
+
+    /// The `did:key` identifier of this key: `did:key:z` followed by the
+    /// base58btc encoding of the `0xed01` multicodec and the key.
+    pub fn to_did_key(&self) -> String {
+        format!(
+            "{}{}",
+            DID_KEY_PREFIX,
+            multibase_encode(MULTICODEC_ED25519_PUB, &self.keynum_pk.pk)
+        )
+    }
+
+    /// Parse a `did:key` identifier of an Ed25519 key.
+    ///
+    /// A `did:key` doesn't include a key identifier, so the returned key has
+    /// an all-zero one. A fragment, as in verification method ids, is ignored.
+    pub fn from_did_key(did: &str) -> Result<PublicKey> {
+        let did = did.split('#').next().unwrap_or(did);
+        let multibase = did
+            .strip_prefix(DID_KEY_PREFIX)
+            .ok_or(PError::InvalidDid("not a did:key identifier"))?;
+        let (codec, key) =
+            multibase_decode(multibase).ok_or(PError::InvalidDid("invalid multibase encoding"))?;
+        if codec != MULTICODEC_ED25519_PUB || key.len() != PUBLICKEY_BYTES {
+            return Err(PError::InvalidDid("not an Ed25519 did:key"));
         }
+        if !ed25519::is_valid_public_key(&key) {
+            return Err(PError::InvalidDid("invalid Ed25519 public key"));
+        }
+        let mut pk = [0u8; PUBLICKEY_BYTES];
+        pk.copy_from_slice(&key);
+        Ok(PublicKey {
+            sig_alg: SIGALG,
+            keynum_pk: KeynumPK {
+                keynum: [0u8; KEYNUM_BYTES],
+                pk,
+            },
+        })
+    }
 
-        if chk_calc != onion_decoded[32..34] {
-            return Err(PError::new(
-                ErrorKind::Io,
-                "Onion checksum incorrect".to_string(),
-            ));
+    /// Export this key as an Ed25519 OKP JSON Web Key (RFC 8037).
+    ///
+    /// `kid` is often the JWK thumbprint, see `jwk_thumbprint()`.
+    pub fn to_jwk(&self, kid: Option<&str>) -> Value {
+        let mut jwk = json!({
+            "kty": "OKP",
+            "crv": "Ed25519",
+            "x": base64url::encode_nopad(&self.keynum_pk.pk),
+        });
+        if let Some(kid) = kid {
+            jwk["kid"] = json!(kid);
         }
+        jwk
+    }
+
+    /// The JWK thumbprint (RFC 7638) of this key: the base64url-encoded
+    /// SHA-256 hash of its required JWK members, in lexicographic order.
+    pub fn jwk_thumbprint(&self) -> String {
+        let members = format!(
+            r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#,
+            base64url::encode_nopad(&self.keynum_pk.pk)
+        );
+        base64url::encode_nopad(&Sha256::digest(members.as_bytes()))
+    }
 
-        // read pubkey
-        for (place, element) in pk.iter_mut().zip(onion_decoded.iter()) {
-            *place = *element;
+    /// Import an Ed25519 OKP JSON Web Key; private members are ignored.
+    ///
+    /// A JWK doesn't include a key identifier, so `keynum` has to be provided,
+    /// or derived with `with_derived_keynum()`.
+    pub fn from_jwk(jwk: &Value, keynum: [u8; KEYNUM_BYTES]) -> Result<PublicKey> {
+        let x = ed25519_jwk_member(jwk, "x")?.ok_or(PError::MissingField("x"))?;
+        if x.len() != PUBLICKEY_BYTES || !ed25519::is_valid_public_key(&x) {
+            return Err(PError::InvalidPublicKey);
         }
+        let mut pk = [0u8; PUBLICKEY_BYTES];
+        pk.copy_from_slice(&x);
+        Ok(PublicKey {
+            sig_alg: SIGALG,
+            keynum_pk: KeynumPK { keynum, pk },
+        })
+    }
 
-        let pk = PublicKey {
+    /// The v3 onion address of the service whose identity key is this key.
+    pub fn to_onion_address(&self) -> OnionAddress {
+        OnionAddress::from_public_key(self)
+    }
+
+    /// Parse a v3 onion address; see `OnionAddress` for the accepted forms.
+    pub fn from_onion_address(
+        onion_addr: &str,
+        sig_alg: [u8; TWOBYTES],
+        keynum: [u8; KEYNUM_BYTES],
+    ) -> Result<PublicKey> {
+        let pk = onion_addr.parse::<OnionAddress>()?.public_key();
+        Ok(PublicKey {
             sig_alg,
-            keynum_pk: KeynumPK { keynum, pk },
-        };
-        Ok(pk)
+            keynum_pk: KeynumPK {
+                keynum,
+                pk: pk.keynum_pk.pk,
+            },
+        })
+    }
+}
+
+/// The onion service time period number at `unix_time` (rend-spec-v3, section 2.2.1).
+///
+/// `period_length` is in minutes, usually `ONION_TIME_PERIOD_LENGTH`, and cannot be zero.
+pub fn onion_time_period(unix_time: u64, period_length: u64) -> Result<u64> {
+    if period_length == 0 {
+        return Err(PError::InvalidTimePeriodLength);
+    }
+    // periods start at 12:00 UTC
+    Ok((unix_time / 60).saturating_sub(12 * 60) / period_length)
+}
+
+pub(crate) fn onion_blinding_param(
+    pk: &[u8; PUBLICKEY_BYTES],
+    time_period: u64,
+    period_length: u64,
+) -> Result<[u8; 32]> {
+    if period_length == 0 {
+        return Err(PError::InvalidTimePeriodLength);
     }
+    // h = H(BLIND_STRING | A | B | N), N = "key-blind" | INT_8(period) | INT_8(period_length)
+    let mut hasher = Sha3_256::new();
+    hasher.update(ONION_BLIND_STRING);
+    hasher.update(pk);
+    hasher.update(ONION_BASEPOINT_STR.as_bytes());
+    hasher.update(b"key-blind");
+    hasher.update(time_period.to_be_bytes());
+    hasher.update(period_length.to_be_bytes());
+    Ok(hasher.finalize().into())
 }
 
 impl cmp::PartialEq for PublicKey {
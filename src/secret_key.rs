@@ -1,18 +1,24 @@
 use crate::base64::{Base64, Decoder, Encoder};
 use crate::constants::*;
 use crate::crypto::blake2b::Blake2b;
+use crate::crypto::ed25519;
 use crate::crypto::util::fixed_time_eq;
 use crate::errors::*;
 use crate::helpers::*;
+use crate::kdf_params::*;
 use crate::keynum::*;
+use crate::password::*;
+use crate::public_key::{ed25519_jwk_member, onion_blinding_param, KeynumDerivation, PublicKey};
 use crate::Result;
+use getrandom::getrandom;
+use serde_json::Value;
 use std::cmp;
 use std::fmt::Write as fmtWrite;
 use std::fmt::{self, Formatter};
 use std::fs;
-use std::io::{self, Write};
-use std::io::{Cursor, Read};
+use std::io::{self, Cursor, Read, Seek};
 use std::path::Path;
+use zeroize::{Zeroize, Zeroizing};
 
 /// A secret key and its metadata.
 ///
@@ -23,18 +29,26 @@ use std::path::Path;
 /// This is what usually gets exported to disk.
 ///
 /// A `SecretKeyBox` can be directly converted to/from a single-line string.
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct SecretKeyBox(String);
 
-impl Into<String> for SecretKeyBox {
-    fn into(self) -> String {
-        self.0
+impl fmt::Debug for SecretKeyBox {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SecretKeyBox")
+            .field(&format_args!("<redacted>"))
+            .finish()
     }
 }
 
-impl Into<SecretKeyBox> for String {
-    fn into(self) -> SecretKeyBox {
-        SecretKeyBox(self)
+impl From<SecretKeyBox> for String {
+    fn from(val: SecretKeyBox) -> Self {
+        val.0
+    }
+}
+
+impl From<String> for SecretKeyBox {
+    fn from(val: String) -> Self {
+        SecretKeyBox(val)
     }
 }
 
@@ -56,7 +70,10 @@ impl SecretKeyBox {
     }
 
     /// Convert a `SecretKeyBox` to a string, for storage.
-    pub fn into_secret_key(self, password: Option<String>) -> Result<SecretKey> {
+    pub fn into_secret_key<P>(self, password: P) -> Result<SecretKey>
+    where
+        P: PasswordProvider,
+    {
         SecretKey::from_box(self, password)
     }
 
@@ -64,6 +81,81 @@ impl SecretKeyBox {
     pub fn to_bytes(&self) -> Vec<u8> {
         self.to_string().as_bytes().to_vec()
     }
+
+    /// The untrusted comment of the secret key.
+    pub fn comment(&self) -> Result<String> {
+        let comment = self
+            .0
+            .lines()
+            .next()
+            .ok_or(PError::MissingField("comment in secret key"))?;
+        Ok(comment
+            .strip_prefix(COMMENT_PREFIX)
+            .unwrap_or(comment)
+            .to_string())
+    }
+
+    /// Returns `true` if the secret key is protected by a password.
+    pub fn is_encrypted(&self) -> Result<bool> {
+        let sk = SecretKey::parse_box(&self.0)?;
+        Ok(sk.kdf_alg != KDF_NONE)
+    }
+
+    /// Change the password protecting the secret key.
+    ///
+    /// The key identifier and the comment are preserved, and a new salt is
+    /// used. An empty new password leaves the secret key unencrypted.
+    /// Unencrypted keys don't require the old password.
+    ///
+    /// # Arguments
+    ///
+    /// * `old_password` - the current password
+    /// * `new_password` - the new password
+    /// * `kdf_params` - the new key derivation parameters. If `None`, the current ones are kept.
+    pub fn change_password<P, Q>(
+        self,
+        old_password: P,
+        new_password: Q,
+        kdf_params: Option<KdfParams>,
+    ) -> Result<SecretKeyBox>
+    where
+        P: PasswordProvider,
+        Q: PasswordProvider,
+    {
+        let comment = self.comment()?;
+        let sk = SecretKey::from_box(self, old_password)?;
+        let kdf_params = match kdf_params {
+            Some(kdf_params) => kdf_params,
+            None => sk.kdf_params()?,
+        };
+        sk.to_encrypted_box(Some(&comment), new_password, kdf_params)
+    }
+}
+
+fn parse_tor_secret_key(bytes: &[u8]) -> Result<Zeroizing<[u8; SECRETKEY_BYTES]>> {
+    if bytes.len() != TOR_SECRETKEY_HEADER.len() + SECRETKEY_BYTES
+        || !bytes.starts_with(TOR_SECRETKEY_HEADER)
+    {
+        return Err(PError::InvalidTorKey);
+    }
+    let mut esk = Zeroizing::new([0u8; SECRETKEY_BYTES]);
+    esk.copy_from_slice(&bytes[TOR_SECRETKEY_HEADER.len()..]);
+    Ok(esk)
+}
+
+/// How an X25519 key-agreement secret is derived from an Ed25519 secret key.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum X25519Derivation {
+    /// The clamped SHA-512 hash of the seed, i.e. the Ed25519 secret scalar.
+    ///
+    /// The X25519 public key is then the Montgomery form of the Ed25519
+    /// public key, and can be computed by anyone who knows the latter.
+    #[default]
+    Standard,
+    /// The raw seed, as used by earlier versions of this crate.
+    ///
+    /// The resulting X25519 public key is unrelated to the Ed25519 public key.
+    Legacy,
 }
 
 /// A `SecretKey` is used to create signatures.
@@ -119,15 +211,10 @@ impl SecretKey {
         }
     }
 
-    pub(crate) fn encrypt(mut self, password: String) -> Result<SecretKey> {
-        let mut stream = [0u8; CHK_BYTES + SECRETKEY_BYTES + KEYNUM_BYTES];
-        let opslimit = load_u64_le(&self.kdf_opslimit_le);
-        let memlimit = load_u64_le(&self.kdf_memlimit_le) as usize;
-        if memlimit > MEMLIMIT_MAX {
-            return Err(PError::new(ErrorKind::KDF, "scrypt parameters too high"));
-        }
-        let params = raw_scrypt_params(memlimit, opslimit, N_LOG2_MAX)?;
-        scrypt::scrypt(&password.as_bytes(), &self.kdf_salt, &params, &mut stream)?;
+    pub(crate) fn encrypt(mut self, password: Password) -> Result<SecretKey> {
+        let mut stream = Zeroizing::new(vec![0u8; CHK_BYTES + SECRETKEY_BYTES + KEYNUM_BYTES]);
+        self.kdf_params()?
+            .derive(password.as_bytes(), &self.kdf_salt, &mut stream)?;
         self.xor_keynum(&stream);
         Ok(self)
     }
@@ -137,6 +224,211 @@ impl SecretKey {
         &self.keynum_sk.keynum[..]
     }
 
+    /// Returns `true` if this key only holds an expanded secret key, with no seed.
+    ///
+    /// Such keys can sign, but cannot be used for conversions that need the seed.
+    pub fn is_expanded(&self) -> bool {
+        self.sig_alg == SIGALG_EXPANDED
+    }
+
+    /// The public key matching this secret key.
+    pub fn public_key(&self) -> PublicKey {
+        let mut pk = [0u8; PUBLICKEY_BYTES];
+        if self.is_expanded() {
+            pk = ed25519::expanded_public_key(&self.keynum_sk.sk);
+        } else {
+            pk.copy_from_slice(&self.keynum_sk.sk[32..64]);
+        }
+        PublicKey {
+            sig_alg: SIGALG,
+            keynum_pk: KeynumPK {
+                keynum: self.keynum_sk.keynum,
+                pk,
+            },
+        }
+    }
+
+    pub(crate) fn seed(&self) -> Result<&[u8]> {
+        if self.is_expanded() {
+            return Err(PError::SeedRequired);
+        }
+        Ok(&self.keynum_sk.sk[0..32])
+    }
+
+    pub(crate) fn expanded_secret_key(&self) -> Zeroizing<[u8; SECRETKEY_BYTES]> {
+        if self.is_expanded() {
+            return Zeroizing::new(self.keynum_sk.sk);
+        }
+        let (mut sk, _, esk) = ed25519::keypair(&self.keynum_sk.sk[0..32]);
+        sk.zeroize();
+        Zeroizing::new(esk)
+    }
+
+    pub(crate) fn sign_raw(&self, message: &[u8], z: Option<&[u8]>) -> [u8; SIGNATURE_BYTES] {
+        if self.is_expanded() {
+            let pk = ed25519::expanded_public_key(&self.keynum_sk.sk);
+            ed25519::signature_expanded(message, &self.keynum_sk.sk, &pk, z)
+        } else {
+            ed25519::signature(message, &self.keynum_sk.sk, z)
+        }
+    }
+
+    pub(crate) fn sign_reader<R>(
+        &self,
+        data_reader: &mut R,
+        z: Option<&[u8]>,
+    ) -> io::Result<[u8; SIGNATURE_BYTES]>
+    where
+        R: Read + Seek,
+    {
+        if self.is_expanded() {
+            let pk = ed25519::expanded_public_key(&self.keynum_sk.sk);
+            ed25519::signature_expanded_reader(data_reader, &self.keynum_sk.sk, &pk, z)
+        } else {
+            ed25519::signature_reader(data_reader, &self.keynum_sk.sk, z)
+        }
+    }
+
+    /// Create an unencrypted `SecretKey` from the content of a Tor
+    /// `hs_ed25519_secret_key` file.
+    ///
+    /// Tor only stores the expanded secret key, so the resulting key can sign
+    /// but has no seed. Tor keys have no key identifier, so `keynum` has to be
+    /// provided. See `to_box()` about saving such keys.
+    pub fn from_tor_secret_key(bytes: &[u8], keynum: [u8; KEYNUM_BYTES]) -> Result<SecretKey> {
+        let esk = parse_tor_secret_key(bytes)?;
+        SecretKey::from_expanded(&esk, keynum)
+    }
+
+    /// Create an unencrypted `SecretKey` from the content of a Tor
+    /// `hs_ed25519_secret_key` file, choosing how the key identifier is derived.
+    ///
+    /// With `KeynumDerivation::PublicKeyHash`, importing the same file again
+    /// gives the same key identifier.
+    pub fn from_tor_secret_key_with_keynum(
+        bytes: &[u8],
+        keynum_derivation: KeynumDerivation,
+    ) -> Result<SecretKey> {
+        let esk = parse_tor_secret_key(bytes)?;
+        let keynum = keynum_derivation.keynum(&ed25519::expanded_public_key(&esk[..]))?;
+        SecretKey::from_expanded(&esk, keynum)
+    }
+
+    /// Create an unencrypted `SecretKey` from an Ed25519 OKP JSON Web Key
+    /// (RFC 8037) with a private `d` member.
+    ///
+    /// If the JWK includes its public key `x`, it must match `d`. JWKs have no
+    /// key identifier, so `keynum` has to be provided.
+    pub fn from_jwk(jwk: &Value, keynum: [u8; KEYNUM_BYTES]) -> Result<SecretKey> {
+        let seed = Zeroizing::new(ed25519_jwk_member(jwk, "d")?.ok_or(PError::MissingField("d"))?);
+        if seed.len() != 32 {
+            return Err(PError::InvalidSeedLength(seed.len()));
+        }
+        let (sk, pk, mut esk) = ed25519::keypair(&seed);
+        esk.zeroize();
+        let sk = Zeroizing::new(sk);
+        if let Some(x) = ed25519_jwk_member(jwk, "x")? {
+            if x != pk {
+                return Err(PError::InvalidJwk(
+                    "the public key doesn't match the private key",
+                ));
+            }
+        }
+        let mut sk = SecretKey {
+            sig_alg: SIGALG,
+            kdf_alg: KDF_NONE,
+            chk_alg: CHK_ALG,
+            kdf_salt: [0u8; KDF_SALTBYTES],
+            kdf_opslimit_le: [0u8; KEYNUM_BYTES],
+            kdf_memlimit_le: [0u8; KEYNUM_BYTES],
+            keynum_sk: KeynumSK {
+                keynum,
+                sk: *sk,
+                chk: [0u8; CHK_BYTES],
+            },
+        };
+        sk.write_checksum()?;
+        Ok(sk)
+    }
+
+    /// Create an unencrypted `SecretKey` from an Ed25519 OKP JSON Web Key,
+    /// choosing how the key identifier is derived.
+    ///
+    /// With `KeynumDerivation::PublicKeyHash`, the key identifier matches the
+    /// one of `PublicKey::from_jwk(..).with_derived_keynum()`.
+    pub fn from_jwk_with_keynum(
+        jwk: &Value,
+        keynum_derivation: KeynumDerivation,
+    ) -> Result<SecretKey> {
+        let mut sk = SecretKey::from_jwk(jwk, [0u8; KEYNUM_BYTES])?;
+        sk.keynum_sk.keynum = keynum_derivation.keynum(&sk.public_key().keynum_pk.pk)?;
+        sk.write_checksum()?;
+        Ok(sk)
+    }
+
+    /// Derive the blinded secret key of an onion service identity key for a time period.
+    ///
+    /// The result is an expanded key matching `PublicKey::blind()`, that can
+    /// sign descriptor signing key certificates.
+    pub fn blind(&self, time_period: u64, period_length: u64) -> Result<SecretKey> {
+        let pk = self.public_key();
+        let param = onion_blinding_param(&pk.keynum_pk.pk, time_period, period_length)?;
+        let esk = Zeroizing::new(ed25519::blind_expanded_secret_key(
+            &*self.expanded_secret_key(),
+            &param,
+        ));
+        SecretKey::from_expanded(&esk, self.keynum_sk.keynum)
+    }
+
+    fn from_expanded(esk: &[u8; SECRETKEY_BYTES], keynum: [u8; KEYNUM_BYTES]) -> Result<SecretKey> {
+        let mut sk = SecretKey {
+            sig_alg: SIGALG_EXPANDED,
+            kdf_alg: KDF_NONE,
+            chk_alg: CHK_ALG,
+            kdf_salt: [0u8; KDF_SALTBYTES],
+            kdf_opslimit_le: [0u8; KEYNUM_BYTES],
+            kdf_memlimit_le: [0u8; KEYNUM_BYTES],
+            keynum_sk: KeynumSK {
+                keynum,
+                sk: *esk,
+                chk: [0u8; CHK_BYTES],
+            },
+        };
+        sk.write_checksum()?;
+        Ok(sk)
+    }
+
+    /// Serialize a decrypted `SecretKey` as a Tor `hs_ed25519_secret_key` file.
+    pub fn to_tor_secret_key(&self) -> Zeroizing<Vec<u8>> {
+        let mut v = Zeroizing::new(TOR_SECRETKEY_HEADER.to_vec());
+        v.extend_from_slice(&*self.expanded_secret_key());
+        v
+    }
+
+    /// Derive an X25519 key-agreement secret from this key.
+    ///
+    /// With `X25519Derivation::Standard`, the matching public key is
+    /// `PublicKey::to_x25519()`. The legacy derivation needs the seed, so it
+    /// doesn't work with expanded secret keys.
+    pub fn to_x25519(&self, derivation: X25519Derivation) -> Result<Zeroizing<[u8; 32]>> {
+        let mut x25519_sk = Zeroizing::new([0u8; 32]);
+        match derivation {
+            X25519Derivation::Standard => {
+                x25519_sk.copy_from_slice(&self.expanded_secret_key()[0..32])
+            }
+            X25519Derivation::Legacy => x25519_sk.copy_from_slice(self.seed()?),
+        }
+        Ok(x25519_sk)
+    }
+
+    /// Export the raw secret key bytes as a lowercase hex string.
+    ///
+    /// The `Debug` representation never includes the secret key; this is the
+    /// explicit way to get at it, e.g. to hand it to another tool.
+    pub fn to_secret_hex(&self) -> Zeroizing<String> {
+        Zeroizing::new(hex::encode(self.keynum_sk.sk))
+    }
+
     /// Deserialize a `SecretKey`.
     ///
     /// For storage, a `SecretKeyBox` is usually what you need instead.
@@ -176,66 +468,123 @@ impl SecretKey {
     ///
     /// For storage, a `SecretKeyBox` is usually what you need instead.
     pub fn to_bytes(&self) -> Vec<u8> {
-        let mut iters = Vec::new();
-        iters.push(self.sig_alg.iter());
-        iters.push(self.kdf_alg.iter());
-        iters.push(self.chk_alg.iter());
-        iters.push(self.kdf_salt.iter());
-        iters.push(self.kdf_opslimit_le.iter());
-        iters.push(self.kdf_memlimit_le.iter());
-        iters.push(self.keynum_sk.keynum.iter());
-        iters.push(self.keynum_sk.sk.iter());
-        iters.push(self.keynum_sk.chk.iter());
+        let iters = [
+            self.sig_alg.iter(),
+            self.kdf_alg.iter(),
+            self.chk_alg.iter(),
+            self.kdf_salt.iter(),
+            self.kdf_opslimit_le.iter(),
+            self.kdf_memlimit_le.iter(),
+            self.keynum_sk.keynum.iter(),
+            self.keynum_sk.sk.iter(),
+            self.keynum_sk.chk.iter(),
+        ];
         let v: Vec<u8> = iters.iter().flat_map(|b| b.clone().cloned()).collect();
         v
     }
 
     /// Convert a `SecretKeyBox` to a `SecretKey`.
-    pub fn from_box(sk_box: SecretKeyBox, password: Option<String>) -> Result<SecretKey> {
-        let s = sk_box.0;
-        let mut lines = s.lines();
-        lines.next().ok_or_else(|| {
-            PError::new(ErrorKind::Io, "Missing comment in secret key".to_string())
-        })?;
-        let encoded_sk = lines.next().ok_or_else(|| {
-            PError::new(
-                ErrorKind::Io,
-                "Missing encoded key in secret key".to_string(),
-            )
-        })?;
-        let sk = SecretKey::from_base64(&encoded_sk)?;
-        let interactive = password.is_none();
-        let password = match password {
-            Some(password) => password,
-            None => {
-                let password = get_password("Password: ")?;
-                write!(
-                    io::stdout(),
-                    "Deriving a key from the password and decrypting the secret key... "
-                )
-                .map_err(|e| PError::new(ErrorKind::Io, e))?;
-                io::stdout().flush()?;
-                password
-            }
+    ///
+    /// `password` can be `Some(password)`, `None` to ask for it interactively,
+    /// or any other `PasswordProvider`.
+    pub fn from_box<P>(sk_box: SecretKeyBox, mut password: P) -> Result<SecretKey>
+    where
+        P: PasswordProvider,
+    {
+        let sk = SecretKey::parse_box(&sk_box.0)?;
+        let encrypted = sk.kdf_alg != KDF_NONE;
+        let sk = if encrypted {
+            let password = request_password(&mut password, PasswordPurpose::Decrypt)?;
+            sk.encrypt(password)?
+        } else {
+            sk
         };
-        let sk = sk.encrypt(password)?;
-        if interactive {
-            writeln!(io::stdout(), "done").map_err(|e| PError::new(ErrorKind::Io, e))?
-        }
-        let checksum_vec = sk.read_checksum().map_err(|e| e)?;
+        let checksum_vec = sk.read_checksum()?;
         let mut chk = [0u8; CHK_BYTES];
         chk.copy_from_slice(&checksum_vec[..]);
-        if chk != sk.keynum_sk.chk {
-            Err(PError::new(
-                ErrorKind::Verify,
-                "Wrong password for that key",
-            ))
-        } else {
+        if chk == sk.keynum_sk.chk {
             Ok(sk)
+        } else if encrypted {
+            Err(PError::WrongPassword)
+        } else {
+            Err(PError::InvalidChecksum)
+        }
+    }
+
+    fn parse_box(s: &str) -> Result<SecretKey> {
+        let mut lines = s.lines();
+        lines
+            .next()
+            .ok_or(PError::MissingField("comment in secret key"))?;
+        let encoded_sk = lines
+            .next()
+            .ok_or(PError::MissingField("encoded key in secret key"))?;
+        SecretKey::from_base64(encoded_sk)
+    }
+
+    /// The key derivation parameters stored with this secret key.
+    ///
+    /// The default parameters are returned for unencrypted keys.
+    pub fn kdf_params(&self) -> Result<KdfParams> {
+        if self.kdf_alg == KDF_NONE {
+            return Ok(KdfParams::default());
         }
+        Ok(KdfParams {
+            alg: KdfAlgorithm::from_tag(self.kdf_alg)?,
+            opslimit: load_u64_le(&self.kdf_opslimit_le),
+            memlimit: load_u64_le(&self.kdf_memlimit_le) as usize,
+        })
+    }
+
+    /// Encrypt a decrypted `SecretKey` with a new password, and convert it to a `SecretKeyBox`.
+    ///
+    /// A new salt is used. An empty password leaves the secret key unencrypted.
+    ///
+    /// # Arguments
+    ///
+    /// * `comment` - an optional untrusted comment to replace the default one
+    /// * `password` - the new password
+    /// * `kdf_params` - the key derivation parameters
+    pub fn to_encrypted_box<P>(
+        &self,
+        comment: Option<&str>,
+        mut password: P,
+        kdf_params: KdfParams,
+    ) -> Result<SecretKeyBox>
+    where
+        P: PasswordProvider,
+    {
+        let password = request_password(&mut password, PasswordPurpose::Encrypt)?;
+        let mut sk = self.clone();
+        if password.is_empty() {
+            sk.kdf_alg = KDF_NONE;
+            sk.kdf_salt = [0u8; KDF_SALTBYTES];
+            sk.kdf_opslimit_le = [0u8; KEYNUM_BYTES];
+            sk.kdf_memlimit_le = [0u8; KEYNUM_BYTES];
+            sk.write_checksum()?;
+            return sk.to_box(comment);
+        }
+        sk.encrypt_with(password, kdf_params)?.to_box(comment)
+    }
+
+    pub(crate) fn encrypt_with(
+        mut self,
+        password: Password,
+        kdf_params: KdfParams,
+    ) -> Result<SecretKey> {
+        self.kdf_alg = kdf_params.alg.tag();
+        getrandom(&mut self.kdf_salt)?;
+        self.kdf_opslimit_le = store_u64_le(kdf_params.opslimit);
+        self.kdf_memlimit_le = store_u64_le(kdf_params.memlimit as u64);
+        self.write_checksum()?;
+        self.encrypt(password)
     }
 
     /// Convert a `SecretKey` to a `SecretKeyBox`.
+    ///
+    /// Expanded secret keys, e.g. imported from Tor, are stored with the
+    /// `EX` algorithm. This is specific to this crate: minisign and rsign
+    /// cannot load such boxes, but signatures made with them are standard.
     pub fn to_box(&self, comment: Option<&str>) -> Result<SecretKeyBox> {
         let mut s = String::new();
         write!(s, "{}", COMMENT_PREFIX)?;
@@ -258,7 +607,11 @@ impl SecretKey {
     }
 
     /// Load a `SecretKeyBox` from a file, and returns a `SecretKey` from it.
-    pub fn from_file<P: AsRef<Path>>(sk_path: P, password: Option<String>) -> Result<SecretKey> {
+    pub fn from_file<P, Q>(sk_path: P, password: Q) -> Result<SecretKey>
+    where
+        P: AsRef<Path>,
+        Q: PasswordProvider,
+    {
         let s = fs::read_to_string(sk_path)?;
         SecretKey::from_box(s.into(), password)
     }
@@ -266,10 +619,17 @@ impl SecretKey {
 
 impl fmt::Debug for SecretKey {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        for byte in self.keynum_sk.sk.iter() {
-            write!(f, "{:x}", byte)?
+        let mut s = f.debug_struct("SecretKey");
+        s.field("sig_alg", &String::from_utf8_lossy(&self.sig_alg))
+            .field("keynum", &format_args!("{:X}", load_u64_le(self.keynum())));
+        if self.kdf_alg == KDF_NONE {
+            s.field("kdf_alg", &format_args!("none"));
+        } else {
+            s.field("kdf_alg", &String::from_utf8_lossy(&self.kdf_alg))
+                .field("kdf_opslimit", &load_u64_le(&self.kdf_opslimit_le))
+                .field("kdf_memlimit", &load_u64_le(&self.kdf_memlimit_le));
         }
-        Ok(())
+        s.field("sk", &format_args!("<redacted>")).finish()
     }
 }
 
@@ -16,15 +16,15 @@ pub struct SignatureBox {
     pub(crate) is_prehashed: bool,
 }
 
-impl Into<String> for SignatureBox {
-    fn into(self) -> String {
-        self.into_string()
+impl From<SignatureBox> for String {
+    fn from(val: SignatureBox) -> Self {
+        val.into_string()
     }
 }
 
-impl Into<SignatureBox> for String {
-    fn into(self) -> SignatureBox {
-        SignatureBox::from_string(&self).unwrap()
+impl From<String> for SignatureBox {
+    fn from(val: String) -> Self {
+        SignatureBox::from_string(&val).unwrap()
     }
 }
 
@@ -42,12 +42,7 @@ impl SignatureBox {
     /// The trusted comment present in the signature.
     pub fn trusted_comment(&self) -> Result<String> {
         let sig_and_trusted_comment = match &self.sig_and_trusted_comment {
-            None => {
-                return Err(PError::new(
-                    ErrorKind::Misc,
-                    "trusted comment is not present",
-                ))
-            }
+            None => return Err(PError::MissingField("trusted comment")),
             Some(sig_and_trusted_comment) => sig_and_trusted_comment,
         };
         if sig_and_trusted_comment.len() < SIGNATURE_BYTES {
@@ -78,19 +73,19 @@ impl SignatureBox {
         let mut lines = s.lines();
         let untrusted_comment = lines
             .next()
-            .ok_or_else(|| PError::new(ErrorKind::Io, "Missing untrusted comment"))?
+            .ok_or(PError::MissingField("untrusted comment"))?
             .to_string();
         let signature_str = lines
             .next()
-            .ok_or_else(|| PError::new(ErrorKind::Io, "Missing signature"))?
+            .ok_or(PError::MissingField("signature"))?
             .to_string();
         let mut trusted_comment_str = lines
             .next()
-            .ok_or_else(|| PError::new(ErrorKind::Io, "Missing trusted comment"))?
+            .ok_or(PError::MissingField("trusted comment"))?
             .to_string();
         let global_sig = lines
             .next()
-            .ok_or_else(|| PError::new(ErrorKind::Io, "Missing global signature"))?
+            .ok_or(PError::MissingField("global signature"))?
             .to_string();
         if !untrusted_comment.starts_with(COMMENT_PREFIX) {
             return Err(PError::new(
@@ -100,7 +95,7 @@ impl SignatureBox {
         }
         let untrusted_comment = untrusted_comment[COMMENT_PREFIX.len()..].to_string();
         let sig_bytes = Base64::decode_to_vec(signature_str.trim().as_bytes())
-            .map_err(|e| PError::new(ErrorKind::Io, e))?;
+            .map_err(|_| PError::InvalidBase64("signature"))?;
         let signature = Signature::from_bytes(&sig_bytes)?;
         if !trusted_comment_str.starts_with(TRUSTED_COMMENT_PREFIX) {
             return Err(PError::new(
@@ -114,12 +109,7 @@ impl SignatureBox {
         let is_prehashed = match signature.sig_alg {
             SIGALG => false,
             SIGALG_PREHASHED => true,
-            _ => {
-                return Err(PError::new(
-                    ErrorKind::Verify,
-                    "Unsupported signature algorithm".to_string(),
-                ))
-            }
+            _ => return Err(PError::UnsupportedAlgorithm(signature.sig_alg)),
         };
         let _ = trusted_comment_str
             .drain(..TRUSTED_COMMENT_PREFIX_LEN)
@@ -127,7 +117,7 @@ impl SignatureBox {
         let mut sig_and_trusted_comment = signature.sig.to_vec();
         sig_and_trusted_comment.extend_from_slice(trusted_comment_str.trim().as_bytes());
         let global_sig = Base64::decode_to_vec(global_sig.trim().as_bytes())
-            .map_err(|e| PError::new(ErrorKind::Io, e))?;
+            .map_err(|_| PError::InvalidBase64("global signature"))?;
         Ok(SignatureBox {
             untrusted_comment,
             signature,
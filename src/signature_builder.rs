@@ -0,0 +1,81 @@
+use crate::constants::*;
+use crate::crypto::blake2b::Blake2b;
+use crate::errors::*;
+use crate::public_key::*;
+use crate::secret_key::*;
+use crate::signature_box::*;
+use crate::signature_box_from_raw;
+use getrandom::getrandom;
+use std::io::{self, Write};
+
+/// Incremental signer.
+///
+/// Data can be fed in arbitrary chunks, either with `update()` or through
+/// the `Write` trait. The data is prehashed, so `finalize()` always returns
+/// a prehashed signature.
+#[derive(Clone)]
+pub struct SignatureBuilder<'a> {
+    pk: Option<&'a PublicKey>,
+    sk: &'a SecretKey,
+    trusted_comment: Option<String>,
+    untrusted_comment: Option<String>,
+    state: Blake2b,
+}
+
+impl<'a> SignatureBuilder<'a> {
+    /// Create a new incremental signer.
+    ///
+    /// # Arguments
+    ///
+    /// * `pk` - an optional public key. If provided, it must be the public key from the original key pair.
+    /// * `sk` - the secret key
+    /// * `trusted_comment` - overrides the default trusted comment
+    /// * `untrusted_comment` - overrides the default untrusted comment
+    pub fn new(
+        pk: Option<&'a PublicKey>,
+        sk: &'a SecretKey,
+        trusted_comment: Option<&str>,
+        untrusted_comment: Option<&str>,
+    ) -> SignatureBuilder<'a> {
+        SignatureBuilder {
+            pk,
+            sk,
+            trusted_comment: trusted_comment.map(ToString::to_string),
+            untrusted_comment: untrusted_comment.map(ToString::to_string),
+            state: Blake2b::new(PREHASH_BYTES),
+        }
+    }
+
+    /// Absorb a chunk of the data to be signed.
+    pub fn update(&mut self, data: &[u8]) {
+        self.state.update(data);
+    }
+
+    /// Sign the data absorbed so far, and return the signature.
+    pub fn finalize(mut self) -> Result<SignatureBox> {
+        let mut h = vec![0u8; PREHASH_BYTES];
+        self.state.finalize(&mut h);
+        let mut z = vec![0; 64];
+        getrandom(&mut z)?;
+        let signature_raw = self.sk.sign_raw(&h, Some(&z));
+        signature_box_from_raw(
+            self.pk,
+            self.sk,
+            signature_raw,
+            true,
+            self.trusted_comment.as_deref(),
+            self.untrusted_comment.as_deref(),
+        )
+    }
+}
+
+impl<'a> Write for SignatureBuilder<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use crate::constants::*;
+use crate::crypto::blake2b::Blake2b;
+use crate::crypto::ed25519::{self, VerifyState};
+use crate::errors::*;
+use crate::public_key::*;
+use crate::signature_box::*;
+use crate::{verify_keynum, verify_trusted_comment};
+use std::io::{self, Write};
+
+#[derive(Clone)]
+enum VerifierState {
+    Prehashed(Blake2b),
+    Legacy(VerifyState),
+}
+
+/// Incremental signature verifier.
+///
+/// Data can be fed in arbitrary chunks, either with `update()` or through
+/// the `Write` trait. Both prehashed and non-prehashed signatures are
+/// supported, and the data never has to be read twice.
+#[derive(Clone)]
+pub struct SignatureVerifier<'a> {
+    pk: &'a PublicKey,
+    signature_box: &'a SignatureBox,
+    state: VerifierState,
+}
+
+impl<'a> SignatureVerifier<'a> {
+    /// Create a new incremental verifier.
+    ///
+    /// An error is returned right away if the signature was not created
+    /// by the given public key.
+    pub fn new(
+        pk: &'a PublicKey,
+        signature_box: &'a SignatureBox,
+    ) -> Result<SignatureVerifier<'a>> {
+        verify_keynum(pk, signature_box)?;
+        let state = if signature_box.is_prehashed() {
+            VerifierState::Prehashed(Blake2b::new(PREHASH_BYTES))
+        } else {
+            VerifierState::Legacy(VerifyState::new(
+                &pk.keynum_pk.pk,
+                &signature_box.signature.sig,
+            ))
+        };
+        Ok(SignatureVerifier {
+            pk,
+            signature_box,
+            state,
+        })
+    }
+
+    /// Absorb a chunk of the signed data.
+    pub fn update(&mut self, data: &[u8]) {
+        match &mut self.state {
+            VerifierState::Prehashed(state) => state.update(data),
+            VerifierState::Legacy(state) => state.update(data),
+        }
+    }
+
+    /// Verify the signature over the data absorbed so far.
+    pub fn finalize(self) -> Result<()> {
+        let valid = match self.state {
+            VerifierState::Prehashed(mut state) => {
+                let mut h = vec![0u8; PREHASH_BYTES];
+                state.finalize(&mut h);
+                ed25519::verify(&h, &self.pk.keynum_pk.pk, &self.signature_box.signature.sig)
+            }
+            VerifierState::Legacy(state) => state.finalize(),
+        };
+        if !valid {
+            return Err(PError::SignatureInvalid);
+        }
+        verify_trusted_comment(self.pk, self.signature_box)
+    }
+}
+
+impl<'a> Write for SignatureVerifier<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
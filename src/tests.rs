@@ -105,6 +105,71 @@ fn signature_prehashed() {
     assert!(verify(&pk, &signature_box, Cursor::new(data), true, false).is_err());
 }
 
+#[test]
+fn signature_stream() {
+    use crate::{sign_stream, verify, KeyPair};
+    use std::io::{Cursor, Seek, SeekFrom};
+
+    let KeyPair { pk, sk, esk: _ } = KeyPair::generate_unencrypted_keypair(None).unwrap();
+    let data = vec![0x42u8; 200_000];
+    let signature_box = sign_stream(Some(&pk), &sk, Cursor::new(&data), false, None, None).unwrap();
+    assert!(!signature_box.is_prehashed());
+    verify(&pk, &signature_box, Cursor::new(&data), true, false).unwrap();
+
+    // the reader is only rewound to where signing started
+    let mut prefixed = b"header".to_vec();
+    prefixed.extend_from_slice(&data);
+    let mut reader = Cursor::new(&prefixed);
+    reader.seek(SeekFrom::Start(6)).unwrap();
+    let signature_box = sign_stream(None, &sk, reader, false, None, None).unwrap();
+    verify(&pk, &signature_box, Cursor::new(&data), true, false).unwrap();
+
+    let mut tampered = data.clone();
+    tampered[150_000] ^= 1;
+    assert!(verify(&pk, &signature_box, Cursor::new(&tampered), true, false).is_err());
+
+    let signature_box = sign_stream(None, &sk, Cursor::new(&data), true, None, None).unwrap();
+    assert!(signature_box.is_prehashed());
+    verify(&pk, &signature_box, Cursor::new(&data), true, false).unwrap();
+}
+
+#[test]
+fn signature_incremental() {
+    use crate::{sign, verify, KeyPair, SignatureBuilder, SignatureVerifier};
+    use std::io::{self, Cursor};
+
+    let KeyPair { pk, sk, esk: _ } = KeyPair::generate_unencrypted_keypair(None).unwrap();
+    let data = vec![0x42u8; 200_000];
+
+    let mut builder = SignatureBuilder::new(Some(&pk), &sk, Some("incremental"), None);
+    for chunk in data.chunks(7_000) {
+        builder.update(chunk);
+    }
+    let signature_box = builder.finalize().unwrap();
+    assert!(signature_box.is_prehashed());
+    assert_eq!(signature_box.trusted_comment().unwrap(), "incremental");
+    verify(&pk, &signature_box, Cursor::new(&data), true, false).unwrap();
+
+    let mut verifier = SignatureVerifier::new(&pk, &signature_box).unwrap();
+    io::copy(&mut Cursor::new(&data), &mut verifier).unwrap();
+    verifier.finalize().unwrap();
+
+    let mut verifier = SignatureVerifier::new(&pk, &signature_box).unwrap();
+    verifier.update(&data[1..]);
+    assert!(verifier.finalize().is_err());
+
+    // non-prehashed signatures can be verified incrementally as well
+    let signature_box = sign(None, &sk, Cursor::new(&data), false, None, None).unwrap();
+    let mut verifier = SignatureVerifier::new(&pk, &signature_box).unwrap();
+    for chunk in data.chunks(3_333) {
+        verifier.update(chunk);
+    }
+    verifier.finalize().unwrap();
+
+    let KeyPair { pk: other_pk, .. } = KeyPair::generate_unencrypted_keypair(None).unwrap();
+    assert!(SignatureVerifier::new(&other_pk, &signature_box).is_err());
+}
+
 #[test]
 fn signature_bones() {
     use crate::{sign, verify, KeyPair, SignatureBones};
@@ -153,6 +218,58 @@ QtKMXWyYcwdpZAlPF7tE2ENJkRd1ujvKjlj1m9RtHTBnZPa5WKU5uWRs5GoP5M/VqE81QFuMKI5k/SfN
     verify(&pk, &signature_box, Cursor::new(bin), false, false).expect("Signature didn't verify");
 }
 
+#[test]
+fn verify_report_det() {
+    use crate::{verify_report, PublicKey, SignatureBones, SignatureBox};
+    use std::io::Cursor;
+
+    let pk =
+        PublicKey::from_base64("RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3").unwrap();
+    let signature_box = SignatureBox::from_string(
+        "untrusted comment: signature from minisign secret key
+RWQf6LRCGA9i59SLOFxz6NxvASXDJeRtuZykwQepbDEGt87ig1BNpWaVWuNrm73YiIiJbq71Wi+dP9eKL8OC351vwIasSSbXxwA=
+trusted comment: timestamp:1555779966\tfile:test
+QtKMXWyYcwdpZAlPF7tE2ENJkRd1ujvKjlj1m9RtHTBnZPa5WKU5uWRs5GoP5M/VqE81QFuMKI5k/SfNQUaOAA==",
+    )
+    .unwrap();
+    let bin = b"test";
+    let mut output = vec![];
+    let report = verify_report(&pk, &signature_box, Cursor::new(bin), Some(&mut output))
+        .expect("Signature didn't verify");
+    assert_eq!(output, bin);
+    assert_eq!(report.keynum(), Some(pk.keynum()));
+    assert_eq!(report.key_id(), Some(0xE7620F1842B4E81F));
+    assert_eq!(&report.sig_alg(), b"Ed");
+    assert!(!report.is_prehashed());
+    assert_eq!(
+        report.trusted_comment(),
+        Some("timestamp:1555779966\tfile:test")
+    );
+    assert_eq!(report.timestamp(), Some(1555779966));
+
+    let signature_bones: SignatureBones = signature_box.into();
+    let report = verify_report(&pk, &signature_bones.into(), Cursor::new(bin), None).unwrap();
+    assert_eq!(report.trusted_comment(), None);
+    assert_eq!(report.timestamp(), None);
+
+    let mut output = vec![];
+    let signature_box = SignatureBox::from_string(
+        "untrusted comment: signature from minisign secret key
+RWQf6LRCGA9i59SLOFxz6NxvASXDJeRtuZykwQepbDEGt87ig1BNpWaVWuNrm73YiIiJbq71Wi+dP9eKL8OC351vwIasSSbXxwA=
+trusted comment: timestamp:1555779966\tfile:test
+QtKMXWyYcwdpZAlPF7tE2ENJkRd1ujvKjlj1m9RtHTBnZPa5WKU5uWRs5GoP5M/VqE81QFuMKI5k/SfNQUaOAA==",
+    )
+    .unwrap();
+    assert!(verify_report(
+        &pk,
+        &signature_box,
+        Cursor::new(b"test2"),
+        Some(&mut output)
+    )
+    .is_err());
+    assert!(output.is_empty());
+}
+
 #[test]
 fn verify_prehashed_det() {
     use crate::{verify, PublicKey, SignatureBox};
@@ -190,7 +307,7 @@ fn test_pubkey_from_onion_address() {
 RWTFh+S84tByDUw+zC2dDGuaX3r3yAePDGhAaoNliwTfDek1ADKuc7Y/L5AKd089Y8k/HuXRRmNPO4cjsmE2dQLu0v7C3DC7SAk=
 trusted comment: fscst5exmlmr262byztwz4kzhggjlzumvc2ndvgytzoucr2tkgxf7mid.onion
 NbTt3wnK1ruWxPFstDT/bineOaX8mVlChY/R8xS9s0ERGfkA7rNDnbSqqJ7jbr8Af0/8ONWi/hRINxwCy6hSDQ==";
-    let signature_box = SignatureBox::from_string(&signature).unwrap();
+    let signature_box = SignatureBox::from_string(signature).unwrap();
 
     let pubkey = PublicKey::from_onion_address(
         onion_address,
@@ -251,14 +368,13 @@ fn test_did_document() {
     {
       "@context": [
         "https://www.w3.org/ns/did/v1",
-        {
-          "@base": "did:onion:hnvcppgow2sc2yvdvdicu3ynonsteflxdxrehjr2ybekdc2z3iu63yid"
-        }
+        "https://w3id.org/security/suites/jws-2020/v1",
+        "https://w3id.org/security/suites/ed25519-2018/v1"
       ],
       "id": "did:onion:hnvcppgow2sc2yvdvdicu3ynonsteflxdxrehjr2ybekdc2z3iu63yid",
-      "VerificationMethod": [
+      "verificationMethod": [
         {
-          "id": "#9ZP03Nu8GrXPAUkbKNxHOKBzxPX83SShgFkRNK-f2lw",
+          "id": "did:onion:hnvcppgow2sc2yvdvdicu3ynonsteflxdxrehjr2ybekdc2z3iu63yid#9ZP03Nu8GrXPAUkbKNxHOKBzxPX83SShgFkRNK-f2lw",
           "type": "JsonWebKey2020",
           "controller": "did:onion:hnvcppgow2sc2yvdvdicu3ynonsteflxdxrehjr2ybekdc2z3iu63yid",
           "publicKeyJwk": {
@@ -268,7 +384,7 @@ fn test_did_document() {
           }
         },
         {
-          "id": "#EmrjlZkIXwQkuI1-1DwU5KNa_Yz-A-8-Ux0mbSDNSDc",
+          "id": "did:onion:hnvcppgow2sc2yvdvdicu3ynonsteflxdxrehjr2ybekdc2z3iu63yid#EmrjlZkIXwQkuI1-1DwU5KNa_Yz-A-8-Ux0mbSDNSDc",
           "type": "JsonWebKey2020",
           "controller": "did:onion:hnvcppgow2sc2yvdvdicu3ynonsteflxdxrehjr2ybekdc2z3iu63yid",
           "publicKeyJwk": {
@@ -278,17 +394,27 @@ fn test_did_document() {
           }
         },
         {
-          "id": "#6JoVelERoTmJKC-3fBuy7ez-L1zIViMtNo6ET9KZ6f8",
+          "id": "did:onion:hnvcppgow2sc2yvdvdicu3ynonsteflxdxrehjr2ybekdc2z3iu63yid#6JoVelERoTmJKC-3fBuy7ez-L1zIViMtNo6ET9KZ6f8",
           "type": "Ed25519VerificationKey2018",
           "controller": "did:onion:hnvcppgow2sc2yvdvdicu3ynonsteflxdxrehjr2ybekdc2z3iu63yid",
           "publicKeyBase58": "4zvwRjXUKGfvwnParsHAS3HuSVzV5cA4McphgmoCtajS"
         }
       ],
-      "authentication": "#9ZP03Nu8GrXPAUkbKNxHOKBzxPX83SShgFkRNK-f2lw",
-      "assertionMethod": "#6JoVelERoTmJKC-3fBuy7ez-L1zIViMtNo6ET9KZ6f8",
-      "capabilityInvocation": "#9ZP03Nu8GrXPAUkbKNxHOKBzxPX83SShgFkRNK-f2lw",
-      "capabilityDelegation": "#9ZP03Nu8GrXPAUkbKNxHOKBzxPX83SShgFkRNK-f2lw",
-      "keyAgreement": "#EmrjlZkIXwQkuI1-1DwU5KNa_Yz-A-8-Ux0mbSDNSDc"
+      "authentication": [
+        "did:onion:hnvcppgow2sc2yvdvdicu3ynonsteflxdxrehjr2ybekdc2z3iu63yid#9ZP03Nu8GrXPAUkbKNxHOKBzxPX83SShgFkRNK-f2lw"
+      ],
+      "assertionMethod": [
+        "did:onion:hnvcppgow2sc2yvdvdicu3ynonsteflxdxrehjr2ybekdc2z3iu63yid#6JoVelERoTmJKC-3fBuy7ez-L1zIViMtNo6ET9KZ6f8"
+      ],
+      "keyAgreement": [
+        "did:onion:hnvcppgow2sc2yvdvdicu3ynonsteflxdxrehjr2ybekdc2z3iu63yid#EmrjlZkIXwQkuI1-1DwU5KNa_Yz-A-8-Ux0mbSDNSDc"
+      ],
+      "capabilityInvocation": [
+        "did:onion:hnvcppgow2sc2yvdvdicu3ynonsteflxdxrehjr2ybekdc2z3iu63yid#9ZP03Nu8GrXPAUkbKNxHOKBzxPX83SShgFkRNK-f2lw"
+      ],
+      "capabilityDelegation": [
+        "did:onion:hnvcppgow2sc2yvdvdicu3ynonsteflxdxrehjr2ybekdc2z3iu63yid#9ZP03Nu8GrXPAUkbKNxHOKBzxPX83SShgFkRNK-f2lw"
+      ]
     });
 
     let seed = vec![0; 32];
@@ -312,6 +438,7 @@ fn test_slip10() {
     use crate::keypair::KeyPair;
     use crate::slip10_generate_xpriv;
     use slip10::*;
+    use std::str::FromStr;
 
     let seed = vec![0; 32];
     let KeyPair { pk: _, sk, esk: _ } =
@@ -378,3 +505,1618 @@ fn test_jwk() {
         serde_json::to_string_pretty(&jwk_expected).unwrap()
     );
 }
+
+#[test]
+fn password_providers() {
+    use crate::{
+        CallbackPasswordProvider, EnvPasswordProvider, KeyPair, PError, PasswordPurpose,
+        ReaderPasswordProvider, SecretKey, StaticPasswordProvider,
+    };
+    use std::env;
+    use std::io::Cursor;
+
+    let mut provider = StaticPasswordProvider::new("key password");
+    let KeyPair { sk, .. } = KeyPair::generate_encrypted_keypair(None, &mut provider).unwrap();
+    assert_eq!(provider.requests(), 1);
+    let sk_box = sk.to_box(None).unwrap();
+
+    let decrypted = SecretKey::from_box(sk_box.clone(), &mut provider).unwrap();
+    assert_eq!(provider.requests(), 2);
+    let decrypted2 = SecretKey::from_box(sk_box.clone(), Some("key password".to_string())).unwrap();
+    assert_eq!(decrypted, decrypted2);
+
+    let mut purposes = vec![];
+    let provider = CallbackPasswordProvider(|purpose| {
+        purposes.push(purpose);
+        Ok("key password".into())
+    });
+    SecretKey::from_box(sk_box.clone(), provider).unwrap();
+    assert_eq!(purposes, vec![PasswordPurpose::Decrypt]);
+
+    let mut provider = ReaderPasswordProvider::new(Cursor::new("wrong\nkey password\r\n"));
+    assert!(matches!(
+        SecretKey::from_box(sk_box.clone(), &mut provider),
+        Err(PError::WrongPassword)
+    ));
+    SecretKey::from_box(sk_box.clone(), &mut provider).unwrap();
+    assert!(matches!(
+        SecretKey::from_box(sk_box.clone(), &mut provider),
+        Err(PError::PasswordUnavailable(_))
+    ));
+
+    env::set_var("MINISIGN_TEST_PASSWORD", "key password");
+    SecretKey::from_box(
+        sk_box.clone(),
+        EnvPasswordProvider::new("MINISIGN_TEST_PASSWORD"),
+    )
+    .unwrap();
+    assert!(matches!(
+        SecretKey::from_box(sk_box, EnvPasswordProvider::new("MINISIGN_TEST_UNSET")),
+        Err(PError::PasswordUnavailable(_))
+    ));
+}
+
+#[test]
+fn change_password() {
+    use crate::{KdfParams, KeyPair, SecretKey, StaticPasswordProvider};
+
+    let KeyPair { sk, .. } =
+        KeyPair::generate_encrypted_keypair(None, Some("old password".to_string())).unwrap();
+    let sk_box = sk.to_box(Some("my key")).unwrap();
+    assert!(sk_box.is_encrypted().unwrap());
+    let original = SecretKey::from_box(sk_box.clone(), Some("old password".to_string())).unwrap();
+
+    let kdf_params = KdfParams::new(65536, 1 << 20).unwrap();
+    let sk_box = sk_box
+        .change_password(
+            Some("old password".to_string()),
+            Some("new password".to_string()),
+            Some(kdf_params),
+        )
+        .unwrap();
+    assert_eq!(sk_box.comment().unwrap(), "my key");
+    assert!(SecretKey::from_box(sk_box.clone(), Some("old password".to_string())).is_err());
+    let sk = SecretKey::from_box(sk_box.clone(), Some("new password".to_string())).unwrap();
+    assert_eq!(sk, original);
+    assert_eq!(sk.keynum(), original.keynum());
+    assert_eq!(sk.kdf_params().unwrap(), kdf_params);
+
+    // an empty password removes the encryption
+    let sk_box = sk_box
+        .change_password(Some("new password".to_string()), Some(String::new()), None)
+        .unwrap();
+    assert!(!sk_box.is_encrypted().unwrap());
+    let mut provider = StaticPasswordProvider::new("unused");
+    let sk = SecretKey::from_box(sk_box.clone(), &mut provider).unwrap();
+    assert_eq!(provider.requests(), 0);
+    assert_eq!(sk, original);
+
+    // and unencrypted keys can be encrypted again
+    let sk_box = sk_box
+        .change_password(
+            &mut provider,
+            Some("password".to_string()),
+            Some(kdf_params),
+        )
+        .unwrap();
+    assert_eq!(provider.requests(), 0);
+    assert!(sk_box.is_encrypted().unwrap());
+    let sk = SecretKey::from_box(sk_box, Some("password".to_string())).unwrap();
+    assert_eq!(sk, original);
+    assert_eq!(sk.keynum(), original.keynum());
+}
+
+#[test]
+fn kdf_params() {
+    use crate::{KdfParams, KeyPair, SecretKey};
+
+    for params in &[
+        KdfParams::interactive(),
+        KdfParams::moderate(),
+        KdfParams::sensitive(),
+    ] {
+        assert_eq!(
+            KdfParams::new(params.opslimit(), params.memlimit()).unwrap(),
+            *params
+        );
+    }
+    assert_eq!(KdfParams::default(), KdfParams::moderate());
+    assert!(KdfParams::sensitive().with_memlimit(1 << 31).is_err());
+
+    let kdf_params = KdfParams::interactive().with_memlimit(1 << 20).unwrap();
+    let KeyPair { sk, .. } = KeyPair::generate_encrypted_keypair_with_kdf(
+        None,
+        Some("password".to_string()),
+        kdf_params,
+    )
+    .unwrap();
+    assert_eq!(sk.kdf_params().unwrap(), kdf_params);
+    let sk_box = sk.to_box(None).unwrap();
+    let sk = SecretKey::from_box(sk_box, Some("password".to_string())).unwrap();
+    assert_eq!(sk.kdf_params().unwrap(), kdf_params);
+}
+
+#[test]
+fn argon2id_kdf() {
+    use crate::{KdfAlgorithm, KdfParams, KeyPair, SecretKey};
+
+    for params in &[
+        KdfParams::argon2id_interactive(),
+        KdfParams::argon2id_moderate(),
+        KdfParams::argon2id_sensitive(),
+    ] {
+        assert_eq!(params.algorithm(), KdfAlgorithm::Argon2id);
+        assert_eq!(
+            KdfParams::new_argon2id(params.opslimit(), params.memlimit()).unwrap(),
+            *params
+        );
+    }
+    assert!(KdfParams::new_argon2id(0, 1 << 20).is_err());
+
+    let kdf_params = KdfParams::new_argon2id(2, 1 << 20).unwrap();
+    let KeyPair { sk, .. } = KeyPair::generate_encrypted_keypair_with_kdf(
+        None,
+        Some("password".to_string()),
+        kdf_params,
+    )
+    .unwrap();
+    let sk_box = sk.to_box(None).unwrap();
+    assert!(SecretKey::from_box(sk_box.clone(), Some("wrong".to_string())).is_err());
+    let sk = SecretKey::from_box(sk_box.clone(), Some("password".to_string())).unwrap();
+    assert_eq!(sk.kdf_params().unwrap(), kdf_params);
+
+    // re-encrypting with scrypt switches the algorithm tag
+    let scrypt_params = KdfParams::new(65536, 1 << 20).unwrap();
+    let sk_box = sk_box
+        .change_password(
+            Some("password".to_string()),
+            Some("password".to_string()),
+            Some(scrypt_params),
+        )
+        .unwrap();
+    let sk2 = SecretKey::from_box(sk_box, Some("password".to_string())).unwrap();
+    assert_eq!(sk2.kdf_params().unwrap(), scrypt_params);
+    assert_eq!(sk, sk2);
+
+    // unknown algorithm tags are rejected
+    let mut bytes = sk.to_bytes();
+    bytes[2..4].copy_from_slice(b"Xx");
+    let sk_box = SecretKey::from_bytes(&bytes).unwrap().to_box(None).unwrap();
+    let err = SecretKey::from_box(sk_box, Some("password".to_string())).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("Unsupported key derivation algorithm"));
+}
+
+#[test]
+fn password_wrapper() {
+    use crate::{KeyPair, Password, SecretKey};
+
+    let password = Password::from("key password");
+    assert_eq!(format!("{:?}", password), "Password(<redacted>)");
+    assert_eq!(password.as_str(), "key password");
+
+    let KeyPair { sk, esk, .. } =
+        KeyPair::generate_encrypted_keypair(None, password.clone()).unwrap();
+    assert_eq!(esk.len(), 64);
+    let sk_box = sk.to_box(None).unwrap();
+    SecretKey::from_box(sk_box.clone(), password).unwrap();
+    assert!(SecretKey::from_box(sk_box, Password::from("wrong")).is_err());
+}
+
+#[test]
+fn debug_redacts_secrets() {
+    use crate::KeyPair;
+
+    let kp = KeyPair::generate_encrypted_keypair(None, "password".to_string()).unwrap();
+    let secret_hex = kp.sk.to_secret_hex();
+    assert_eq!(secret_hex.len(), 128);
+    assert_eq!(
+        hex::decode(secret_hex.as_str()).unwrap(),
+        kp.sk.keynum_sk.sk
+    );
+
+    let debug = format!("{:?}", kp);
+    assert!(!debug.contains(secret_hex.as_str()));
+    assert!(!debug.contains(&hex::encode(&kp.sk.keynum_sk.sk[..32])));
+    assert!(debug.contains("<redacted>"));
+    assert!(debug.contains("kdf_alg: \"Sc\""));
+
+    let sk_debug = format!("{:?}", kp.sk);
+    let keynum = format!("{:X}", crate::helpers::load_u64_le(kp.sk.keynum()));
+    assert!(sk_debug.contains(&keynum));
+    assert!(format!("{:?}", kp.sk.keynum_sk).contains(&keynum));
+
+    let sk_box = kp.sk.to_box(None).unwrap();
+    assert_eq!(format!("{:?}", sk_box), "SecretKeyBox(<redacted>)");
+}
+
+#[test]
+fn error_variants() {
+    use crate::{
+        sign, verify, ErrorKind, KdfParams, KeyPair, PError, PublicKey, SecretKey, SignatureBox,
+        SIGALG,
+    };
+    use std::error::Error;
+    use std::io::Cursor;
+
+    let KeyPair { pk, sk, .. } =
+        KeyPair::generate_encrypted_keypair(None, "password".to_string()).unwrap();
+    let err = SecretKey::from_box(sk.to_box(None).unwrap(), "wrong".to_string()).unwrap_err();
+    assert!(matches!(err, PError::WrongPassword));
+    assert_eq!(err.kind(), ErrorKind::Verify);
+    assert!(err.source().is_none());
+
+    let other = KeyPair::generate_unencrypted_keypair(None).unwrap();
+    let data = b"test";
+    let signature_box = sign(None, &other.sk, Cursor::new(data), false, None, None).unwrap();
+    match verify(&pk, &signature_box, Cursor::new(data), true, false).unwrap_err() {
+        PError::KeyIdMismatch { sig, pk: pk_id } => {
+            assert_eq!(sig, crate::helpers::load_u64_le(other.pk.keynum()));
+            assert_eq!(pk_id, crate::helpers::load_u64_le(pk.keynum()));
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+    let err = verify(&other.pk, &signature_box, Cursor::new(b"tset"), true, false).unwrap_err();
+    assert!(matches!(err, PError::SignatureInvalid));
+
+    let mut onion = pk.to_onion_address().to_string();
+    onion.replace_range(52..53, if &onion[52..53] == "a" { "b" } else { "a" });
+    let err = PublicKey::from_onion_address(&onion, SIGALG, [0u8; 8]).unwrap_err();
+    assert!(matches!(err, PError::InvalidOnionChecksum));
+    assert_eq!(err.kind(), ErrorKind::Encoding);
+
+    let err = PublicKey::from_base64(&pk.to_base64()[..55]).unwrap_err();
+    assert!(matches!(
+        err,
+        PError::InvalidBase64Length {
+            expected: 56,
+            got: 55
+        }
+    ));
+
+    let err = PublicKey::from_base64(&"!".repeat(56)).unwrap_err();
+    assert!(matches!(err, PError::InvalidBase64("public key")));
+    let mut lines: Vec<_> = signature_box
+        .to_string()
+        .lines()
+        .map(String::from)
+        .collect();
+    lines[1] = "!!!!".to_string();
+    assert!(matches!(
+        SignatureBox::from_string(&lines.join("\n")),
+        Err(PError::InvalidBase64("signature"))
+    ));
+
+    // an unencrypted key has no password to get wrong
+    let unencrypted = other
+        .sk
+        .to_encrypted_box(None, String::new(), KdfParams::default())
+        .unwrap();
+    let mut corrupted = SecretKey::from_box(unencrypted, None).unwrap();
+    corrupted.keynum_sk.chk[0] ^= 1;
+    let err = SecretKey::from_box(corrupted.to_box(None).unwrap(), None).unwrap_err();
+    assert!(matches!(err, PError::InvalidChecksum));
+    assert_eq!(err.kind(), ErrorKind::Verify);
+
+    let err = PError::from(std::io::Error::other("disk on fire"));
+    assert_eq!(err.kind(), ErrorKind::Io);
+    assert_eq!(err.source().unwrap().to_string(), "disk on fire");
+}
+
+#[test]
+fn tor_secret_key_import() {
+    use crate::crypto::ed25519;
+    use crate::{
+        convert_secret_to_onion_keys, sign, verify, KeyPair, PError, PublicKey, SecretKey,
+        SignatureBox,
+    };
+    use std::io::Cursor;
+
+    let KeyPair { pk, sk, esk } = KeyPair::generate_unencrypted_keypair(None).unwrap();
+    let message = b"test";
+    assert_eq!(
+        ed25519::signature_expanded(message, &esk, &pk.keynum_pk.pk, None),
+        ed25519::signature(message, &sk.keynum_sk.sk, None)
+    );
+
+    let (mut tor_sk, mut tor_pk, mut hostname) = (vec![], vec![], vec![]);
+    convert_secret_to_onion_keys(&mut tor_sk, &mut tor_pk, &mut hostname, sk.clone()).unwrap();
+    assert_eq!(tor_sk.len(), 96);
+    assert_eq!(tor_pk.len(), 64);
+
+    let mut keynum = [0u8; 8];
+    keynum.copy_from_slice(pk.keynum());
+    let tor_secret = SecretKey::from_tor_secret_key(&tor_sk, keynum).unwrap();
+    let tor_public = PublicKey::from_tor_public_key(&tor_pk, keynum).unwrap();
+    assert!(tor_secret.is_expanded());
+    assert_eq!(tor_public, pk);
+    assert_eq!(tor_secret.public_key(), pk);
+    assert_eq!(tor_secret.to_tor_secret_key().as_slice(), tor_sk.as_slice());
+    assert_eq!(tor_public.to_tor_public_key(), tor_pk);
+
+    // the expanded key survives a round trip through a box, and still signs
+    let sk_box = tor_secret.to_box(None).unwrap();
+    let tor_secret = SecretKey::from_box(sk_box, None).unwrap();
+    assert!(tor_secret.is_expanded());
+    for prehashed in [false, true] {
+        let signature_box = sign(
+            None,
+            &tor_secret,
+            Cursor::new(message),
+            prehashed,
+            None,
+            None,
+        )
+        .unwrap();
+        verify(&pk, &signature_box, Cursor::new(message), true, false).unwrap();
+        // the signature is a standard one, that can be saved and loaded back
+        let signature_box = SignatureBox::from_string(&signature_box.to_string()).unwrap();
+        assert_eq!(signature_box.is_prehashed(), prehashed);
+        verify(&pk, &signature_box, Cursor::new(message), true, false).unwrap();
+    }
+
+    // exporting back to Tor works, conversions needing the seed don't
+    let (mut tor_sk2, mut tor_pk2, mut hostname2) = (vec![], vec![], vec![]);
+    convert_secret_to_onion_keys(
+        &mut tor_sk2,
+        &mut tor_pk2,
+        &mut hostname2,
+        tor_secret.clone(),
+    )
+    .unwrap();
+    assert_eq!(
+        (tor_sk2, tor_pk2, hostname2),
+        (tor_sk.clone(), tor_pk, hostname)
+    );
+    assert!(matches!(
+        crate::convert_secret_to_jwk(vec![], tor_secret),
+        Err(PError::SeedRequired)
+    ));
+
+    tor_sk[0] = b'X';
+    assert!(matches!(
+        SecretKey::from_tor_secret_key(&tor_sk, keynum),
+        Err(PError::InvalidTorKey)
+    ));
+    assert!(SecretKey::from_tor_secret_key(&tor_sk[..95], keynum).is_err());
+}
+
+#[test]
+fn tor_descriptor_signing_cert() {
+    use crate::{KeyPair, PError, TorCert, TorCertType};
+
+    let blinded = KeyPair::generate_unencrypted_keypair(None).unwrap();
+    let desc_signing = KeyPair::generate_unencrypted_keypair(None).unwrap();
+    let expiration = 1_600_000_000;
+    let cert = TorCert::new_descriptor_signing(&desc_signing.pk, &blinded.sk, expiration).unwrap();
+    assert_eq!(cert.cert_type(), TorCertType::HsV3DescSigning);
+    assert_eq!(cert.expiration(), expiration / 3600 * 3600);
+    assert!(cert.is_expired_at(expiration));
+    assert!(!cert.is_expired_at(expiration - 3600));
+    assert_eq!(cert.certified_key(), &desc_signing.pk.keynum_pk.pk);
+    assert_eq!(cert.signing_key(), Some(&blinded.pk.keynum_pk.pk[..]));
+    cert.verify(None).unwrap();
+    cert.verify(Some(&blinded.pk)).unwrap();
+    assert!(matches!(
+        cert.verify(Some(&desc_signing.pk)),
+        Err(PError::TorCertSignatureInvalid)
+    ));
+
+    // cert-spec.txt layout: header, certified key, one 32-byte extension, signature
+    let bytes = cert.to_bytes();
+    assert_eq!(bytes.len(), 7 + 32 + 1 + 4 + 32 + 64);
+    assert_eq!(&bytes[..2], &[0x01, 0x08]);
+    assert_eq!(&bytes[40..44], &[0x00, 0x20, 0x04, 0x00]);
+    assert_eq!(TorCert::from_bytes(&bytes).unwrap(), cert);
+
+    let pem = cert.to_pem();
+    assert!(pem.starts_with("-----BEGIN ED25519 CERT-----\n"));
+    assert!(pem.lines().all(|line| line.len() <= 64));
+    assert_eq!(TorCert::from_pem(&pem).unwrap(), cert);
+
+    let mut tampered = bytes.clone();
+    tampered[10] ^= 1;
+    assert!(matches!(
+        TorCert::from_bytes(&tampered).unwrap().verify(None),
+        Err(PError::TorCertSignatureInvalid)
+    ));
+    assert!(TorCert::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+    // descriptor-signing-key-cert of the v3 descriptor in stem's test data
+    let pem = "-----BEGIN ED25519 CERT-----
+AQgABl5/AZLmgPpXVS59SEydKj7bRvvAduVOqQt3u4Tj5tVlfVKhAQAgBABUhpfe
+/Wd3p/M74DphsGcIMee/npQ9BTzkzCyTyVmDbykek2EciWaOTCVZJVyiKPErngfW
+BDwQZ8rhp05oCqhhY3oFHqG9KS7HGzv9g2v1/PrVJMbkfpwu1YK4b3zIZAk=
+-----END ED25519 CERT-----
+";
+    let cert = TorCert::from_pem(pem).unwrap();
+    assert_eq!(cert.cert_type(), TorCertType::HsV3DescSigning);
+    assert_eq!(cert.expiration(), 1_502_665_200);
+    assert_eq!(
+        hex::encode(cert.certified_key()),
+        "92e680fa57552e7d484c9d2a3edb46fbc076e54ea90b77bb84e3e6d5657d52a1"
+    );
+    assert_eq!(
+        hex::encode(cert.signing_key().unwrap()),
+        "548697defd6777a7f33be03a61b0670831e7bf9e943d053ce4cc2c93c959836f"
+    );
+    cert.verify(None).unwrap();
+    assert_eq!(cert.to_pem(), pem);
+}
+
+#[test]
+fn onion_key_blinding() {
+    use crate::crypto::ed25519;
+    use crate::public_key::onion_blinding_param;
+    use crate::{
+        onion_time_period, sign, verify, KeyPair, PError, SecretKey, TorCert,
+        ONION_TIME_PERIOD_LENGTH,
+    };
+    use curve25519_dalek::edwards::CompressedEdwardsY;
+    use curve25519_dalek::scalar::Scalar;
+    use std::io::Cursor;
+
+    // rend-spec-v3 2.2.1: 2016-04-13 11:15:01 UTC is in time period 16903
+    for (unix_time, period) in [
+        (1_460_546_101, 16903),
+        (1_460_548_799, 16903),
+        (1_460_548_800, 16904),
+        (1_460_635_200, 16905),
+    ] {
+        assert_eq!(
+            onion_time_period(unix_time, ONION_TIME_PERIOD_LENGTH).unwrap(),
+            period
+        );
+    }
+    assert!(matches!(
+        onion_time_period(1_460_546_101, 0),
+        Err(PError::InvalidTimePeriodLength)
+    ));
+
+    // Tor's src/test/ed25519_vectors.inc: secret key, blinding parameter,
+    // blinded expanded secret key and blinded public key
+    let vectors = [
+        (
+            "26c76712d89d906e6672dafa614c42e5cb1caac8c6568e4d2493087db51f0d36",
+            "54a513898b471d1d448a2f3c55c1de2c0ef718c447b04497eeb999ed32027823",
+            "293c3acff4e902f6f63ddc5d5caa2a57e771db4f24de65d4c28df3232f47fa01\
+             171d43f24e3f53e70ec7ac280044ac77d4942dee5d6807118a59bdf3ee647e89",
+            "1fc1fa4465bd9d4956fdbdc9d3acb3c7019bb8d5606b951c2e1dfe0b42eaeb41",
+        ),
+        (
+            "fba7a5366b5cb98c2667a18783f5cf8f4f8d1a2ce939ad22a6e685edde85128d",
+            "831e9b5325b5d31b7ae6197e9c7a7baf2ec361e08248bce055908971047a2347",
+            "38b88f9f9440358da544504ee152fb475528f7c51c285bd1c68b14ade8e29a07\
+             b8ceff20dfcf53eb52b891fc078c934efbf0353af7242e7dc51bb32a093afa29",
+            "1cbbd4a88ce8f165447f159d9f628ada18674158c4f7c5ead44ce8eb0fa6eb7e",
+        ),
+    ];
+    for (seed, param, blinded_esk, blinded_pk) in vectors.iter() {
+        let (_, pk, esk) = ed25519::keypair(&hex::decode(seed).unwrap());
+        let param = hex::decode(param).unwrap();
+        assert_eq!(
+            hex::encode(ed25519::blind_expanded_secret_key(&esk, &param)),
+            *blinded_esk
+        );
+        assert_eq!(
+            hex::encode(ed25519::blind_public_key(&pk, &param).unwrap()),
+            *blinded_pk
+        );
+    }
+
+    // the blinding parameter of an identity key for a time period, from C tor
+    // (as used by arti's tor-hscrypto tests)
+    let esk = hex::decode(
+        "D8C7FF0E31295B66540D789AF3E3DF992038A9592EEA01D8B7CBA06D6E66D159\
+         4D6167696320576F7264733A20737065697373636F62616C742062697669756D",
+    )
+    .unwrap();
+    let mut esk_bytes = [0u8; 64];
+    esk_bytes.copy_from_slice(&esk);
+    let identity_pk = ed25519::expanded_public_key(&esk);
+    assert_eq!(
+        hex::encode_upper(identity_pk),
+        "833990B085C1A688C1D4C8B1F6B56AFAF5A2ECA674449E1D704F83765CCB7BC6"
+    );
+    let param = onion_blinding_param(&identity_pk, 1234, 1440).unwrap();
+    assert_eq!(
+        hex::encode_upper(param),
+        "379E50DB31FEE6775ABD0AF6FB7C371E060308F4F847DB09FE4CFE13AF602287"
+    );
+    let tor_sk = SecretKey::from_tor_secret_key(
+        &[&crate::TOR_SECRETKEY_HEADER[..], &esk_bytes[..]].concat(),
+        [0u8; 8],
+    )
+    .unwrap();
+    assert_eq!(
+        hex::encode_upper(tor_sk.public_key().blind(1234, 1440).unwrap().keynum_pk.pk),
+        "3A50BF210E8F9EE955AE0014F7A6917FB65EBF098A86305ABB508D1A7291B6D5"
+    );
+    assert_eq!(
+        tor_sk.blind(1234, 1440).unwrap().public_key().keynum_pk.pk,
+        tor_sk.public_key().blind(1234, 1440).unwrap().keynum_pk.pk
+    );
+    assert!(matches!(
+        tor_sk.blind(1234, 0),
+        Err(PError::InvalidTimePeriodLength)
+    ));
+
+    let KeyPair { pk, sk, .. } = KeyPair::generate_unencrypted_keypair(None).unwrap();
+    let period = 16903;
+    let blinded_pk = pk.blind(period, ONION_TIME_PERIOD_LENGTH).unwrap();
+    let blinded_sk = sk.blind(period, ONION_TIME_PERIOD_LENGTH).unwrap();
+    assert_eq!(blinded_sk.public_key(), blinded_pk);
+    assert!(blinded_pk != pk);
+    assert!(pk.blind(period + 1, ONION_TIME_PERIOD_LENGTH).unwrap() != blinded_pk);
+
+    // cross-check the point multiplication against curve25519-dalek
+    let mut h = onion_blinding_param(&pk.keynum_pk.pk, period, ONION_TIME_PERIOD_LENGTH).unwrap();
+    h[0] &= 248;
+    h[31] &= 63;
+    h[31] |= 64;
+    let expected =
+        CompressedEdwardsY(pk.keynum_pk.pk).decompress().unwrap() * Scalar::from_bytes_mod_order(h);
+    assert_eq!(expected.compress().to_bytes(), blinded_pk.keynum_pk.pk);
+
+    // the blinded key signs for the blinded public key
+    let signature_box = sign(None, &blinded_sk, Cursor::new(b"test"), false, None, None).unwrap();
+    verify(
+        &blinded_pk,
+        &signature_box,
+        Cursor::new(b"test"),
+        true,
+        false,
+    )
+    .unwrap();
+    let desc_signing = KeyPair::generate_unencrypted_keypair(None).unwrap();
+    TorCert::new_descriptor_signing(&desc_signing.pk, &blinded_sk, 1_600_000_000)
+        .unwrap()
+        .verify(Some(&blinded_pk))
+        .unwrap();
+
+    // keys imported from Tor blind to the same keys
+    let mut keynum = [0u8; 8];
+    keynum.copy_from_slice(pk.keynum());
+    let tor_sk = SecretKey::from_tor_secret_key(&sk.to_tor_secret_key(), keynum).unwrap();
+    let tor_blinded_sk = tor_sk.blind(period, ONION_TIME_PERIOD_LENGTH).unwrap();
+    assert_eq!(tor_blinded_sk.to_bytes(), blinded_sk.to_bytes());
+}
+
+#[test]
+fn x25519_conversion() {
+    use crate::{
+        convert_secret_to_tor_auth_keys, convert_secret_to_tor_auth_keys_with_derivation, KeyPair,
+        PublicKey, SecretKey, X25519Derivation, SIGALG,
+    };
+    use x25519_dalek::{PublicKey as X25519PublicKey, StaticSecret};
+
+    let KeyPair { pk, sk, .. } = KeyPair::generate_unencrypted_keypair(None).unwrap();
+    let x25519_sk = sk.to_x25519(X25519Derivation::Standard).unwrap();
+    let x25519_pk = X25519PublicKey::from(&StaticSecret::from(*x25519_sk));
+    assert_eq!(pk.to_x25519().unwrap(), x25519_pk.to_bytes());
+
+    // a peer who only knows the onion address derives the same key
+    let onion_pk =
+        PublicKey::from_onion_address(&pk.to_onion_address().to_string(), SIGALG, [0u8; 8])
+            .unwrap();
+    assert_eq!(onion_pk.to_x25519().unwrap(), x25519_pk.to_bytes());
+
+    // both parties agree on a shared secret
+    let peer = StaticSecret::from([7u8; 32]);
+    assert_eq!(
+        StaticSecret::from(*x25519_sk)
+            .diffie_hellman(&X25519PublicKey::from(&peer))
+            .to_bytes(),
+        peer.diffie_hellman(&X25519PublicKey::from(pk.to_x25519().unwrap()))
+            .to_bytes()
+    );
+
+    // the legacy derivation uses the seed as is
+    let legacy_sk = sk.to_x25519(X25519Derivation::Legacy).unwrap();
+    assert_eq!(&legacy_sk[..], &sk.keynum_sk.sk[..32]);
+    assert!(
+        X25519PublicKey::from(&StaticSecret::from(*legacy_sk)).to_bytes() != x25519_pk.to_bytes()
+    );
+
+    // expanded keys only support the standard derivation
+    let mut keynum = [0u8; 8];
+    keynum.copy_from_slice(pk.keynum());
+    let tor_sk = SecretKey::from_tor_secret_key(&sk.to_tor_secret_key(), keynum).unwrap();
+    assert_eq!(
+        tor_sk.to_x25519(X25519Derivation::Standard).unwrap(),
+        x25519_sk
+    );
+    assert!(tor_sk.to_x25519(X25519Derivation::Legacy).is_err());
+
+    let hostname = pk.to_onion_address().to_string();
+    let (mut auth_sk, mut auth_pk) = (vec![], vec![]);
+    convert_secret_to_tor_auth_keys_with_derivation(
+        &mut auth_sk,
+        &mut auth_pk,
+        &hostname,
+        sk.clone(),
+        X25519Derivation::Standard,
+    )
+    .unwrap();
+    let expected = base32::encode(
+        base32::Alphabet::RFC4648 { padding: false },
+        x25519_pk.as_bytes(),
+    );
+    assert_eq!(
+        String::from_utf8(auth_pk).unwrap(),
+        format!("descriptor:x25519:{}", expected)
+    );
+    // the default derivation is still the legacy one
+    let (mut legacy_auth_sk, mut legacy_auth_pk) = (vec![], vec![]);
+    convert_secret_to_tor_auth_keys(&mut legacy_auth_sk, &mut legacy_auth_pk, &hostname, sk)
+        .unwrap();
+    let expected = base32::encode(
+        base32::Alphabet::RFC4648 { padding: false },
+        X25519PublicKey::from(&StaticSecret::from(*legacy_sk)).as_bytes(),
+    );
+    assert_eq!(
+        String::from_utf8(legacy_auth_pk).unwrap(),
+        format!("descriptor:x25519:{}", expected)
+    );
+    assert!(legacy_auth_sk != auth_sk);
+}
+
+#[test]
+fn tor_client_auth_files() {
+    use crate::{
+        convert_secret_to_tor_auth_keys, convert_secret_to_tor_auth_keys_with_derivation, KeyPair,
+        PError, TorClientAuth, TorClientAuthPrivate, X25519Derivation,
+    };
+
+    let KeyPair { pk, sk, .. } = KeyPair::generate_unencrypted_keypair(None).unwrap();
+    let hostname = pk.to_onion_address().to_string();
+    let (mut auth_private, mut auth) = (vec![], vec![]);
+    convert_secret_to_tor_auth_keys_with_derivation(
+        &mut auth_private,
+        &mut auth,
+        &hostname,
+        sk.clone(),
+        X25519Derivation::Standard,
+    )
+    .unwrap();
+    let auth_private = String::from_utf8(auth_private).unwrap();
+    let auth = String::from_utf8(auth).unwrap();
+
+    let parsed_private = TorClientAuthPrivate::from_string(&auth_private).unwrap();
+    let parsed = TorClientAuth::from_string(&format!("{}\n", auth)).unwrap();
+    assert_eq!(parsed_private.onion_address().public_key(), pk);
+    assert_eq!(parsed.public_key(), &pk.to_x25519().unwrap());
+    assert!(parsed_private.matches(&parsed));
+    assert_eq!(parsed_private.to_string().as_str(), auth_private);
+    assert_eq!(parsed.to_string(), auth);
+    assert!(format!("{:?}", parsed_private).contains("<redacted>"));
+
+    // the hostname may be given without the .onion suffix
+    let without_suffix =
+        TorClientAuthPrivate::new(&hostname[..56], &sk, X25519Derivation::Standard).unwrap();
+    assert_eq!(without_suffix.to_string(), parsed_private.to_string());
+    let other = KeyPair::generate_unencrypted_keypair(None).unwrap();
+    let other_auth =
+        TorClientAuthPrivate::new(&hostname, &other.sk, X25519Derivation::Standard).unwrap();
+    assert!(!other_auth.matches(&parsed));
+
+    // errors, not panics, on malformed input
+    let (mut sk_out, mut pk_out) = (vec![], vec![]);
+    assert!(matches!(
+        convert_secret_to_tor_auth_keys(&mut sk_out, &mut pk_out, "short", sk.clone()),
+        Err(PError::InvalidOnionLength(5))
+    ));
+    let mut bad_checksum = hostname.clone();
+    bad_checksum.replace_range(52..53, if &hostname[52..53] == "a" { "b" } else { "a" });
+    assert!(matches!(
+        TorClientAuthPrivate::new(&bad_checksum, &sk, X25519Derivation::Standard),
+        Err(PError::InvalidOnionChecksum)
+    ));
+    let non_ascii = format!("{}é.onion", &hostname[..54]);
+    assert!(TorClientAuthPrivate::new(&non_ascii, &sk, X25519Derivation::Standard).is_err());
+    assert!(matches!(
+        TorClientAuth::from_string("descriptor:ed25519:AAAA"),
+        Err(PError::InvalidTorClientAuth(_))
+    ));
+    assert!(TorClientAuth::from_string("descriptor:x25519:AAAA").is_err());
+    assert!(TorClientAuthPrivate::from_string(&auth).is_err());
+    assert!(TorClientAuthPrivate::from_string("").is_err());
+}
+
+#[test]
+fn onion_address() {
+    use crate::{OnionAddress, PError, PublicKey};
+    use std::str::FromStr;
+
+    let canonical = "fscst5exmlmr262byztwz4kzhggjlzumvc2ndvgytzoucr2tkgxf7mid.onion";
+    let addr = OnionAddress::from_str(canonical).unwrap();
+    assert_eq!(addr.to_string(), canonical);
+    assert_eq!(addr.without_suffix(), &canonical[..56]);
+    assert_eq!(addr.version(), 3);
+    assert_eq!(
+        addr.public_key().to_base64(),
+        "RWQAAAAAAAAAACyFKfSXYtkde0HGZ2zxWTmMleaMqLTR1NieXUFHU1Gu"
+    );
+    assert_eq!(addr.public_key().to_onion_address(), addr);
+
+    for s in [
+        "FSCST5EXMLMR262BYZTWZ4KZHGGJLZUMVC2NDVGYTZOUCR2TKGXF7MID.ONION",
+        "fscst5exmlmr262byztwz4kzhggjlzumvc2ndvgytzoucr2tkgxf7mid",
+        "www.fscst5exmlmr262byztwz4kzhggjlzumvc2ndvgytzoucr2tkgxf7mid.onion",
+        "a.b.fscst5exmlmr262byztwz4kzhggjlzumvc2ndvgytzoucr2tkgxf7mid.onion.",
+        " fscst5exmlmr262byztwz4kzhggjlzumvc2ndvgytzoucr2tkgxf7mid.onion\n",
+    ] {
+        assert_eq!(s.parse::<OnionAddress>().unwrap(), addr, "{}", s);
+    }
+    let pk =
+        PublicKey::from_onion_address(&canonical.to_uppercase(), crate::SIGALG, [1u8; 8]).unwrap();
+    assert_eq!(pk.keynum(), &[1u8; 8]);
+
+    // the checksum is the one encoded in the address
+    let decoded = base32::decode(
+        base32::Alphabet::RFC4648 { padding: false },
+        &canonical[..56],
+    )
+    .unwrap();
+    assert_eq!(addr.checksum(), decoded[32..34]);
+
+    let mut bad_version = decoded.clone();
+    bad_version[34] = 2;
+    let bad_version = base32::encode(base32::Alphabet::RFC4648 { padding: false }, &bad_version);
+    assert!(matches!(
+        bad_version.parse::<OnionAddress>(),
+        Err(PError::InvalidOnionVersion(2))
+    ));
+    assert!(matches!(
+        "fscst5exmlmr262byztwz4kzhggjlzumvc2ndvgytzoucr2tkkxf7mid.onion".parse::<OnionAddress>(),
+        Err(PError::InvalidOnionChecksum)
+    ));
+    assert!(matches!(
+        "fscst5exmlmr262byztwz4kzhggjlzumvc2ndvgytzoucr2tkgxf7mid.example.onion"
+            .parse::<OnionAddress>(),
+        Err(PError::InvalidOnionLength(7))
+    ));
+    assert!(matches!(
+        "fscst5exmlmr262byztwz4kzhggjlzumvc2ndvgytzoucr2tkgxf7mi1.onion".parse::<OnionAddress>(),
+        Err(PError::InvalidOnionEncoding)
+    ));
+    assert!(matches!(
+        "fscst5exmlmr262byztwz4kzhggjlzumvc2ndvgytzoucr2tkgxf7mé.onion".parse::<OnionAddress>(),
+        Err(PError::InvalidOnionEncoding)
+    ));
+}
+
+#[test]
+fn vanity_search() {
+    use crate::{PError, VanitySearch};
+
+    let search = VanitySearch::new(Some("AB"), Some("c")).unwrap().threads(2);
+    assert_eq!(search.expected_attempts(), 32.0 * 32.0 * 16.0);
+    let kp = search.run(|_| {}).unwrap().unwrap();
+    assert!(search.is_match(&kp.pk));
+    assert!(kp.pk.to_onion_address().to_string().starts_with("ab"));
+    assert!(kp
+        .pk
+        .to_box()
+        .unwrap()
+        .to_string()
+        .contains("public key: C"));
+    assert_eq!(kp.sk.public_key(), kp.pk);
+
+    // cancelled from the progress callback
+    let search = VanitySearch::new(Some("aaaaaaaaaaaaaaaaaaaa"), None).unwrap();
+    let cancel = search.cancel_handle();
+    let mut calls = 0;
+    let res = search
+        .run(|progress| {
+            calls += 1;
+            assert!(progress.attempts > 0);
+            assert_eq!(progress.expected_attempts, 32f64.powi(20));
+            cancel.cancel();
+        })
+        .unwrap();
+    assert!(res.is_none());
+    assert_eq!(calls, 1);
+
+    // a search cancelled before it starts returns at once
+    let unstarted = VanitySearch::new(Some("aaaaaaaaaaaaaaaaaaaa"), None).unwrap();
+    unstarted.cancel_handle().cancel();
+    assert!(unstarted
+        .run(|_| panic!("not cancelled"))
+        .unwrap()
+        .is_none());
+
+    // a reset search can be run again, and cancelled through a clone of the handle
+    let cancel = cancel.clone();
+    cancel.reset();
+    let mut calls = 0;
+    let res = search
+        .run(|_| {
+            calls += 1;
+            if calls == 2 {
+                cancel.cancel();
+            }
+        })
+        .unwrap();
+    assert!(res.is_none());
+    assert_eq!(calls, 2);
+
+    // the workers stop when the progress callback panics
+    let search = VanitySearch::new(Some("aaaaaaaaaaaaaaaaaaaa"), None).unwrap();
+    let res = std::panic::catch_unwind(|| search.run(|_| panic!("progress")));
+    assert!(res.is_err());
+
+    VanitySearch::new(Some(&"a".repeat(51)), None).unwrap();
+    for (onion, keynum) in [
+        (Some("a1"), None),
+        (Some(&"a".repeat(52)[..]), None),
+        (None, Some("xy")),
+        (None, Some("0A")),
+        (None, Some("12345678123456781")),
+    ] {
+        assert!(matches!(
+            VanitySearch::new(onion, keynum),
+            Err(PError::InvalidVanityPattern(_))
+        ));
+    }
+}
+
+#[test]
+fn verify_with_onion_address() {
+    use crate::{sign, verify_onion, KeyPair, OnionAddress, PError};
+    use std::io::Cursor;
+
+    let KeyPair { pk, sk, .. } = KeyPair::generate_unencrypted_keypair(None).unwrap();
+    let data = b"published by an onion service";
+    let signature_box = sign(Some(&pk), &sk, Cursor::new(data), true, None, None).unwrap();
+
+    // only the hostname is known to the verifier
+    let hostname = pk.to_onion_address().to_string().to_uppercase();
+    let onion_address: OnionAddress = hostname.parse().unwrap();
+    let mut output = vec![];
+    let report = verify_onion(
+        &onion_address,
+        &signature_box,
+        Cursor::new(data),
+        Some(&mut output),
+    )
+    .unwrap();
+    assert_eq!(report.keynum(), None);
+    assert_eq!(output, data);
+
+    let err = verify_onion(
+        &onion_address,
+        &signature_box,
+        Cursor::new(b"tampered"),
+        None,
+    )
+    .unwrap_err();
+    assert!(matches!(err, PError::SignatureInvalid));
+    let other = KeyPair::generate_unencrypted_keypair(None).unwrap();
+    let err = verify_onion(
+        &other.pk.to_onion_address(),
+        &signature_box,
+        Cursor::new(data),
+        None,
+    )
+    .unwrap_err();
+    assert!(matches!(err, PError::SignatureInvalid));
+}
+
+#[test]
+fn deterministic_keynum() {
+    use crate::{slip10_generate_keypair, verify, KdfParams, KeyPair, KeynumDerivation, SecretKey};
+    use std::io::Cursor;
+
+    let seed = vec![7u8; 32];
+    let kp1 = KeyPair::generate_unencrypted_keypair_with_keynum(
+        Some(seed.clone()),
+        KeynumDerivation::PublicKeyHash,
+    )
+    .unwrap();
+    let kp2 = KeyPair::generate_unencrypted_keypair_with_keynum(
+        Some(seed.clone()),
+        KeynumDerivation::PublicKeyHash,
+    )
+    .unwrap();
+    assert_eq!(
+        kp1.pk.to_box().unwrap().to_string(),
+        kp2.pk.to_box().unwrap().to_string()
+    );
+    assert_eq!(kp1.sk.keynum(), kp1.pk.keynum());
+    assert_eq!(kp1.sk.keynum(), kp2.sk.keynum());
+    let random = KeyPair::generate_unencrypted_keypair(Some(seed.clone())).unwrap();
+    assert_eq!(random.pk, kp1.pk);
+    assert_ne!(random.pk.keynum(), kp1.pk.keynum());
+
+    // the identifier can be recovered from the onion address
+    let from_onion = kp1.pk.to_onion_address().public_key().with_derived_keynum();
+    assert_eq!(from_onion.keynum(), kp1.pk.keynum());
+    let signature_box =
+        crate::sign(None, &kp1.sk, Cursor::new(b"backup"), false, None, None).unwrap();
+    verify(
+        &from_onion,
+        &signature_box,
+        Cursor::new(b"backup"),
+        true,
+        false,
+    )
+    .unwrap();
+
+    let encrypted = KeyPair::generate_encrypted_keypair_with_keynum(
+        Some(seed.clone()),
+        Some("pwd".to_string()),
+        KdfParams::new(65536, 1 << 20).unwrap(),
+        KeynumDerivation::PublicKeyHash,
+    )
+    .unwrap();
+    assert_eq!(encrypted.pk.keynum(), kp1.pk.keynum());
+
+    let child1 = slip10_generate_keypair(
+        None,
+        Some(seed.clone()),
+        "m/0H/1H",
+        KeynumDerivation::PublicKeyHash,
+    )
+    .unwrap();
+    let child2 = slip10_generate_keypair(
+        Some(kp1.sk.clone()),
+        None,
+        "m/0H/1H",
+        KeynumDerivation::PublicKeyHash,
+    )
+    .unwrap();
+    assert_eq!(child1.pk.keynum(), child2.pk.keynum());
+    assert_ne!(child1.pk, kp1.pk);
+
+    let tor_sk = kp1.sk.to_tor_secret_key();
+    let imported =
+        SecretKey::from_tor_secret_key_with_keynum(&tor_sk, KeynumDerivation::PublicKeyHash)
+            .unwrap();
+    assert_eq!(imported.keynum(), kp1.pk.keynum());
+    assert_eq!(imported.public_key().keynum(), kp1.pk.keynum());
+}
+
+#[test]
+fn did_document_builder() {
+    use crate::{DidDocument, DidService, KeyPair, VerificationMethod, VerificationRelationship};
+    use serde_json::json;
+
+    let kp = KeyPair::generate_unencrypted_keypair(Some(vec![0; 32])).unwrap();
+    let other = KeyPair::generate_unencrypted_keypair(None).unwrap();
+    let did = "did:onion:hnvcppgow2sc2yvdvdicu3ynonsteflxdxrehjr2ybekdc2z3iu63yid";
+
+    let doc = DidDocument::onion(&kp.pk)
+        .unwrap()
+        .onion_http_service()
+        .unwrap()
+        .service(DidService::new(
+            "#mirror",
+            "LinkedDomains",
+            "https://example.com/",
+        ))
+        .controller("did:example:admin")
+        .also_known_as("https://example.com/")
+        .verification_method(
+            VerificationMethod::ed25519_jwk(&other.pk)
+                .with_fragment("backup")
+                .with_controller("did:example:admin"),
+            &[VerificationRelationship::CapabilityDelegation],
+        );
+    assert_eq!(doc.id(), did);
+    let json = doc.to_json();
+    assert_eq!(json["controller"], json!("did:example:admin"));
+    assert_eq!(json["alsoKnownAs"], json!(["https://example.com/"]));
+    assert_eq!(json["verificationMethod"].as_array().unwrap().len(), 4);
+    assert_eq!(
+        json["verificationMethod"][3]["id"],
+        json!(format!("{}#backup", did))
+    );
+    assert_eq!(
+        json["verificationMethod"][3]["controller"],
+        json!("did:example:admin")
+    );
+    assert_eq!(json["capabilityDelegation"].as_array().unwrap().len(), 2);
+    assert!(json["authentication"].is_array());
+    assert_eq!(
+        json["verificationMethod"][1]["publicKeyJwk"]["x"],
+        json!(data_encoding::base64url::encode_nopad(
+            &kp.pk.to_x25519().unwrap()
+        ))
+    );
+    assert_eq!(
+        json["service"],
+        json!([
+            {
+                "id": format!("{}#onion", did),
+                "type": "LinkedDomains",
+                "serviceEndpoint": format!("http://{}.onion/", &did[10..]),
+            },
+            {
+                "id": format!("{}#mirror", did),
+                "type": "LinkedDomains",
+                "serviceEndpoint": "https://example.com/",
+            }
+        ])
+    );
+    let parsed: serde_json::Value = serde_json::from_str(&doc.to_string()).unwrap();
+    assert_eq!(parsed, json);
+
+    assert!(DidDocument::new("did:example:123")
+        .onion_http_service()
+        .is_err());
+}
+
+#[test]
+fn did_onion_resolution() {
+    use crate::{
+        DidDocument, KeyPair, PError, VerificationKey, VerificationMethod,
+        VerificationRelationship, X25519Derivation,
+    };
+
+    let kp = KeyPair::generate_unencrypted_keypair(None).unwrap();
+    let doc = DidDocument::onion(&kp.pk)
+        .unwrap()
+        .onion_http_service()
+        .unwrap();
+    let did = doc.id().to_string();
+    let published = doc.to_string();
+
+    let resolved = DidDocument::resolve_onion(&did, &published).unwrap();
+    assert_eq!(resolved, doc);
+    let auth = resolved
+        .verification_methods_for(VerificationRelationship::Authentication)
+        .next()
+        .unwrap();
+    assert_eq!(auth.key().unwrap(), VerificationKey::Ed25519(kp.pk.clone()));
+    let agreement = resolved
+        .verification_methods_for(VerificationRelationship::KeyAgreement)
+        .next()
+        .unwrap();
+    assert_eq!(
+        agreement.key().unwrap(),
+        VerificationKey::X25519(kp.pk.to_x25519().unwrap())
+    );
+    assert_eq!(
+        resolved.services()[0].endpoint(),
+        format!("http://{}/", kp.pk.to_onion_address())
+    );
+
+    // relative identifiers, and single strings for relationships
+    let relative = published
+        .replace(&format!("\"{}#", did), "\"#")
+        .replace('[', "[ ");
+    let mut relative_json: serde_json::Value = serde_json::from_str(&relative).unwrap();
+    relative_json["authentication"] = relative_json["authentication"][0].clone();
+    let resolved = DidDocument::resolve_onion(&did, &relative_json.to_string()).unwrap();
+    assert_eq!(resolved, doc);
+
+    // JWKs with members in another order, and extra members
+    let mut reordered: serde_json::Value = serde_json::from_str(&published).unwrap();
+    for method in reordered["verificationMethod"].as_array_mut().unwrap() {
+        if let Some(jwk) = method.get("publicKeyJwk").cloned() {
+            method["publicKeyJwk"] = serde_json::json!({
+                "kty": jwk["kty"],
+                "x": jwk["x"],
+                "crv": jwk["crv"],
+                "kid": "key-1",
+            });
+        }
+    }
+    let resolved = DidDocument::resolve_onion(&did, &reordered.to_string()).unwrap();
+    assert_eq!(
+        resolved
+            .verification_methods_for(VerificationRelationship::Authentication)
+            .next()
+            .unwrap()
+            .key()
+            .unwrap(),
+        VerificationKey::Ed25519(kp.pk.clone())
+    );
+
+    // a mirror handing control to another DID
+    let mut controlled: serde_json::Value = serde_json::from_str(&published).unwrap();
+    controlled["controller"] = serde_json::json!("did:example:attacker");
+    assert!(matches!(
+        DidDocument::resolve_onion(&did, &controlled.to_string()),
+        Err(PError::InvalidDidDocument(_))
+    ));
+    controlled["controller"] = serde_json::json!(did);
+    DidDocument::resolve_onion(&did, &controlled.to_string()).unwrap();
+    controlled["verificationMethod"][0]["controller"] = serde_json::json!("did:example:attacker");
+    assert!(matches!(
+        DidDocument::resolve_onion(&did, &controlled.to_string()),
+        Err(PError::InvalidDidDocument(_))
+    ));
+
+    // a mirror substituting its own keys
+    let other = KeyPair::generate_unencrypted_keypair(None).unwrap();
+    let other_doc = DidDocument::onion(&other.pk).unwrap();
+    let forged = other_doc.to_string().replace(other_doc.id(), &did);
+    assert!(matches!(
+        DidDocument::resolve_onion(&did, &forged),
+        Err(PError::DidKeyMismatch(_))
+    ));
+    assert!(matches!(
+        DidDocument::resolve_onion(other_doc.id(), &published),
+        Err(PError::InvalidDidDocument(_))
+    ));
+    let renamed = DidDocument::onion(&kp.pk).unwrap().verification_method(
+        VerificationMethod::ed25519_jwk(&kp.pk).with_fragment("custom"),
+        &[],
+    );
+    assert!(matches!(
+        DidDocument::resolve_onion(&did, &renamed.to_string()),
+        Err(PError::DidFragmentMismatch(_))
+    ));
+    let mut legacy = vec![];
+    crate::generate_did_document_with_derivation(&mut legacy, kp.sk, X25519Derivation::Legacy)
+        .unwrap();
+    assert!(matches!(
+        DidDocument::resolve_onion(&did, std::str::from_utf8(&legacy).unwrap()),
+        Err(PError::DidKeyMismatch(_))
+    ));
+
+    let mut dangling: serde_json::Value = serde_json::from_str(&published).unwrap();
+    dangling["assertionMethod"] = serde_json::json!(["#unknown"]);
+    assert!(matches!(
+        DidDocument::resolve_onion(&did, &dangling.to_string()),
+        Err(PError::InvalidDidDocument(_))
+    ));
+    assert!(matches!(
+        DidDocument::resolve_onion(
+            &did.to_uppercase().replace("DID:ONION", "did:onion"),
+            &published
+        ),
+        Err(PError::InvalidDid(_))
+    ));
+    assert!(matches!(
+        DidDocument::resolve_onion("did:web:example.com", &published),
+        Err(PError::InvalidDid(_))
+    ));
+    assert!(DidDocument::resolve_onion(&did, "not json").is_err());
+}
+
+#[test]
+fn did_key() {
+    use crate::{generate_did_key_document, DidDocument, KeyPair, PError, PublicKey};
+
+    // did:key specification test vector
+    let kp = KeyPair::generate_unencrypted_keypair(Some(vec![0; 32])).unwrap();
+    let did = "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp";
+    assert_eq!(kp.pk.to_did_key(), did);
+    assert_eq!(PublicKey::from_did_key(did).unwrap(), kp.pk);
+    assert_eq!(
+        PublicKey::from_did_key(&format!("{}#z6Mk", did)).unwrap(),
+        kp.pk
+    );
+
+    let doc = DidDocument::did_key(&kp.pk).unwrap();
+    let json = doc.to_json();
+    assert_eq!(
+        json["verificationMethod"][0]["id"],
+        format!("{}#z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp", did)
+    );
+    assert_eq!(
+        json["verificationMethod"][0]["publicKeyMultibase"],
+        "z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp"
+    );
+    let montgomery = curve25519_dalek::edwards::CompressedEdwardsY(kp.sk.public_key().keynum_pk.pk)
+        .decompress()
+        .unwrap()
+        .to_montgomery();
+    let mut x25519_multicodec = vec![0xec, 0x01];
+    x25519_multicodec.extend_from_slice(montgomery.as_bytes());
+    let x25519_multibase = format!("z{}", bs58::encode(x25519_multicodec).into_string());
+    assert_eq!(
+        json["keyAgreement"][0],
+        format!("{}#{}", did, x25519_multibase)
+    );
+    assert!(x25519_multibase.starts_with("z6LS"));
+    assert_eq!(json["assertionMethod"], json["authentication"]);
+    let parsed = DidDocument::from_json(&json).unwrap();
+    assert_eq!(parsed, doc);
+    for method in parsed.verification_methods() {
+        method.key().unwrap();
+    }
+
+    let mut written = vec![];
+    generate_did_key_document(&mut written, kp.sk).unwrap();
+    assert_eq!(String::from_utf8(written).unwrap(), doc.to_string());
+
+    // X25519 keys are not signing keys
+    assert!(matches!(
+        PublicKey::from_did_key(&format!("did:key:{}", x25519_multibase)),
+        Err(PError::InvalidDid(_))
+    ));
+    assert!(
+        PublicKey::from_did_key("did:key:6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp").is_err()
+    );
+    assert!(PublicKey::from_did_key("did:onion:z6MkiTBz1ymuepAQ4HEHYSF1H8qu").is_err());
+
+    // the identity point is not a valid Ed25519 key
+    let mut identity = [0u8; 32];
+    identity[0] = 1;
+    let mut identity_multicodec = vec![0xed, 0x01];
+    identity_multicodec.extend_from_slice(&identity);
+    let identity_multibase = format!("z{}", bs58::encode(identity_multicodec).into_string());
+    assert!(matches!(
+        PublicKey::from_did_key(&format!("did:key:{}", identity_multibase)),
+        Err(PError::InvalidDid(_))
+    ));
+    let mut weak = json.clone();
+    weak["verificationMethod"][0]["publicKeyMultibase"] = identity_multibase.into();
+    let weak = DidDocument::from_json(&weak).unwrap();
+    assert!(matches!(
+        weak.verification_methods().next().unwrap().key(),
+        Err(PError::InvalidDidDocument(_))
+    ));
+}
+
+#[test]
+fn jws_eddsa() {
+    use crate::{DidDocument, Jws, KeyPair, PError, SecretKey, VerificationRelationship};
+    use data_encoding::base64url;
+    use serde_json::json;
+
+    // RFC 8037, appendix A.4
+    let seed = base64url::decode_nopad(b"nWGxne_9WmC6hEr0kuwsxERJxWl7MmkZcDusAxyuf2A").unwrap();
+    let kp = KeyPair::generate_unencrypted_keypair(Some(seed)).unwrap();
+    assert_eq!(
+        base64url::encode_nopad(&kp.pk.keynum_pk.pk),
+        "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"
+    );
+    let jws = Jws::sign(b"Example of Ed25519 signing", &kp.sk, None).unwrap();
+    let compact = "eyJhbGciOiJFZERTQSJ9.RXhhbXBsZSBvZiBFZDI1NTE5IHNpZ25pbmc.hgyY0il_MGCjP0JzlnLWG1PPOt7-09PGcvMg3AIbQR6dWbhijcNR4ki4iylGjg5BhVsPt9g7sVvpAr_MuM0KAg";
+    assert_eq!(jws.to_compact().unwrap(), compact);
+    let parsed = Jws::from_compact(compact).unwrap();
+    assert_eq!(parsed, jws);
+    assert_eq!(parsed.payload().unwrap(), b"Example of Ed25519 signing");
+    parsed.verify(&kp.pk, None).unwrap();
+    let parsed = Jws::from_json(&jws.to_json().unwrap()).unwrap();
+    parsed.verify(&kp.pk, None).unwrap();
+
+    // detached, unencoded payload signed by an onion identity
+    let doc = DidDocument::onion(&kp.pk).unwrap();
+    let method = doc
+        .verification_methods_for(VerificationRelationship::AssertionMethod)
+        .next()
+        .unwrap();
+    let kid = doc.verification_method_id(method);
+    let payload = b"{\"file\": \"release.tar.gz\"}";
+    let jws = Jws::sign_detached(payload, &kp.sk, Some(&kid)).unwrap();
+    assert!(jws.is_detached());
+    let compact = jws.to_compact().unwrap();
+    assert!(compact.contains(".."));
+    let parsed = Jws::from_compact(&compact).unwrap();
+    assert_eq!(parsed.kid(), Some(kid.as_str()));
+    assert_eq!(parsed.header()["b64"], json!(false));
+    let resolved = DidDocument::resolve_onion(doc.id(), &doc.to_string()).unwrap();
+    parsed.verify_with_did(&resolved, Some(payload)).unwrap();
+    assert!(matches!(
+        parsed.verify_with_did(&resolved, Some(b"tampered")),
+        Err(PError::JwsSignatureInvalid)
+    ));
+    assert!(matches!(
+        parsed.verify(&kp.pk, None),
+        Err(PError::InvalidJws(_))
+    ));
+    let general = json!({"signatures": [jws.to_json().unwrap()]});
+    Jws::from_json(&general)
+        .unwrap()
+        .verify(&kp.pk, Some(payload))
+        .unwrap();
+
+    // expanded secret keys, as imported from Tor, sign the same way
+    let tor_sk = SecretKey::from_tor_secret_key(&kp.sk.to_tor_secret_key(), [0u8; 8]).unwrap();
+    assert_eq!(
+        Jws::sign_detached(payload, &tor_sk, Some(&kid)).unwrap(),
+        jws
+    );
+
+    let other = KeyPair::generate_unencrypted_keypair(None).unwrap();
+    assert!(matches!(
+        parsed.verify(&other.pk, Some(payload)),
+        Err(PError::JwsSignatureInvalid)
+    ));
+    let none_alg = format!(
+        "{}.{}",
+        base64url::encode_nopad(b"{\"alg\":\"none\"}"),
+        compact.split_once('.').unwrap().1
+    );
+    assert!(matches!(
+        Jws::from_compact(&none_alg)
+            .unwrap()
+            .verify(&kp.pk, Some(payload)),
+        Err(PError::InvalidJws(_))
+    ));
+    let unknown_crit = Jws::from_compact(&format!(
+        "{}..{}",
+        base64url::encode_nopad(b"{\"alg\":\"EdDSA\",\"crit\":[\"exp\"],\"exp\":0}"),
+        compact.rsplit_once('.').unwrap().1
+    ))
+    .unwrap();
+    assert!(matches!(
+        unknown_crit.verify(&kp.pk, Some(payload)),
+        Err(PError::InvalidJws(_))
+    ));
+    assert!(Jws::from_compact("a.b").is_err());
+    assert!(Jws::from_compact("!!.e30.AA").is_err());
+}
+
+#[test]
+fn verifiable_credentials() {
+    use crate::credential::jcs;
+    use crate::helpers::xsd_date_time;
+    use crate::{
+        issue_credential, sign_presentation, verify_credential, verify_presentation,
+        DataIntegrityProof, DidDocument, KeyPair, PError, VerificationRelationship,
+    };
+    use serde_json::json;
+
+    // RFC 8785, section 3.2.2
+    let numbers = json!([
+        333_333_333.333_333_3,
+        1E30,
+        4.50,
+        2e-3,
+        0.000000000000000000000000001,
+        -0.0,
+        100
+    ]);
+    assert_eq!(
+        jcs(&numbers),
+        "[333333333.3333333,1e+30,4.5,0.002,1e-27,0,100]"
+    );
+    let object = json!({"\u{20ac}": 1, "\r": 2, "1": 3, "\u{1f600}": 4, "\u{fb33}": 5, "a": ["\n", "\u{1}"]});
+    assert_eq!(
+        jcs(&object),
+        "{\"\\r\":2,\"1\":3,\"a\":[\"\\n\",\"\\u0001\"],\"\u{20ac}\":1,\"\u{1f600}\":4,\"\u{fb33}\":5}"
+    );
+    assert_eq!(xsd_date_time(1555779966), "2019-04-20T17:06:06Z");
+    assert_eq!(xsd_date_time(951782400), "2000-02-29T00:00:00Z");
+
+    // Data Integrity EdDSA Cryptosuites v1.0, eddsa-jcs-2022 example
+    let secret = bs58::decode("3u2en7t5LR2WtQH5PfFqMqwVHBeXouLzo6haApm8XHqvjxq")
+        .into_vec()
+        .unwrap();
+    assert_eq!(secret[..2], [0x80, 0x26]);
+    let spec_key = KeyPair::generate_unencrypted_keypair(Some(secret[2..].to_vec())).unwrap();
+    assert_eq!(
+        spec_key.pk.to_did_key(),
+        "did:key:z6MkrJVnaZkeFzdQyMZu1cgjg7k1pZZ6pvBQ7XJPt4swbTQ2"
+    );
+    let alumni_credential = json!({
+        "@context": [
+            "https://www.w3.org/ns/credentials/v2",
+            "https://www.w3.org/ns/credentials/examples/v2"
+        ],
+        "id": "urn:uuid:58172aac-d8ba-11ed-83dd-0b3aef56cc33",
+        "type": ["VerifiableCredential", "AlumniCredential"],
+        "name": "Alumni Credential",
+        "description": "A minimum viable example of an Alumni Credential.",
+        "issuer": "https://vc.example/issuers/5678",
+        "validFrom": "2023-01-01T00:00:00Z",
+        "credentialSubject": {
+            "id": "did:example:abcdefgh",
+            "alumniOf": "The School of Examples"
+        }
+    });
+    let spec_doc = DidDocument::did_key(&spec_key.pk).unwrap();
+    let signed = DataIntegrityProof::new(
+        "did:key:z6MkrJVnaZkeFzdQyMZu1cgjg7k1pZZ6pvBQ7XJPt4swbTQ2#z6MkrJVnaZkeFzdQyMZu1cgjg7k1pZZ6pvBQ7XJPt4swbTQ2",
+        VerificationRelationship::AssertionMethod,
+    )
+    .with_created("2023-02-24T23:36:38Z")
+    .sign(&alumni_credential, &spec_key.sk)
+    .unwrap();
+    assert_eq!(
+        signed["proof"]["proofValue"],
+        "z2HnFSSPPBzR36zdDgK8PbEHeXbR56YF24jwMpt3R1eHXQzJDMWS93FCzpvJpwTWd3GAVFuUfjoJdcnTMuVor51aX"
+    );
+    DataIntegrityProof::verify(&signed, &spec_doc).unwrap();
+
+    let issuer = KeyPair::generate_unencrypted_keypair(Some(vec![1u8; 32])).unwrap();
+    let issuer_doc = DidDocument::onion(&issuer.pk).unwrap();
+    let holder = KeyPair::generate_unencrypted_keypair(Some(vec![2u8; 32])).unwrap();
+    let holder_doc = DidDocument::did_key(&holder.pk).unwrap();
+
+    let credential = json!({
+        "@context": ["https://www.w3.org/2018/credentials/v1"],
+        "type": ["VerifiableCredential"],
+        "issuer": issuer_doc.id(),
+        "issuanceDate": "2021-01-01T00:00:00Z",
+        "credentialSubject": {"id": holder_doc.id(), "memberOf": "torgap"}
+    });
+    let vc = issue_credential(&credential, &issuer.sk, &issuer_doc).unwrap();
+    assert_eq!(
+        vc["@context"],
+        json!([
+            "https://www.w3.org/2018/credentials/v1",
+            "https://w3id.org/security/data-integrity/v2"
+        ])
+    );
+    assert_eq!(vc["proof"]["@context"], vc["@context"]);
+    assert_eq!(vc["proof"]["cryptosuite"], "eddsa-jcs-2022");
+    let proof = verify_credential(&vc, &issuer_doc).unwrap();
+    assert_eq!(proof.purpose(), VerificationRelationship::AssertionMethod);
+    let method = issuer_doc
+        .verification_methods_for(VerificationRelationship::AssertionMethod)
+        .next()
+        .unwrap();
+    assert_eq!(
+        proof.verification_method(),
+        issuer_doc.verification_method_id(method)
+    );
+    // the proof doesn't depend on the order of the properties
+    let reordered: serde_json::Value = serde_json::from_str(&jcs(&vc)).unwrap();
+    verify_credential(&reordered, &issuer_doc).unwrap();
+    // nor on the way the DID document was obtained
+    let resolved = DidDocument::resolve_onion(issuer_doc.id(), &issuer_doc.to_string()).unwrap();
+    verify_credential(&vc, &resolved).unwrap();
+
+    let mut tampered = vc.clone();
+    tampered["credentialSubject"]["memberOf"] = json!("someone else");
+    assert!(matches!(
+        verify_credential(&tampered, &issuer_doc),
+        Err(PError::ProofSignatureInvalid)
+    ));
+    assert!(matches!(
+        verify_credential(&vc, &holder_doc),
+        Err(PError::InvalidCredential(_))
+    ));
+    assert!(matches!(
+        issue_credential(&credential, &holder.sk, &issuer_doc),
+        Err(PError::InvalidProof(_))
+    ));
+    // the key agreement key cannot make assertions
+    let mut wrong_method = vc.clone();
+    let x25519 = issuer_doc
+        .verification_methods_for(VerificationRelationship::KeyAgreement)
+        .next()
+        .unwrap();
+    wrong_method["proof"]["verificationMethod"] = json!(issuer_doc.verification_method_id(x25519));
+    assert!(matches!(
+        verify_credential(&wrong_method, &issuer_doc),
+        Err(PError::InvalidProof(_))
+    ));
+
+    let presentation = json!({
+        "@context": ["https://www.w3.org/ns/credentials/v2"],
+        "type": "VerifiablePresentation",
+        "holder": holder_doc.id(),
+        "verifiableCredential": [vc]
+    });
+    let vp = sign_presentation(
+        &presentation,
+        &holder.sk,
+        &holder_doc,
+        Some("nonce-1234"),
+        Some("example.onion"),
+    )
+    .unwrap();
+    assert_eq!(vp["@context"], presentation["@context"]);
+    let proof =
+        verify_presentation(&vp, &holder_doc, Some("nonce-1234"), Some("example.onion")).unwrap();
+    assert_eq!(proof.purpose(), VerificationRelationship::Authentication);
+    assert_eq!(proof.challenge(), Some("nonce-1234"));
+    verify_credential(&vp["verifiableCredential"][0], &issuer_doc).unwrap();
+    assert!(matches!(
+        verify_presentation(&vp, &holder_doc, Some("nonce-5678"), Some("example.onion")),
+        Err(PError::InvalidProof(_))
+    ));
+    // optional properties that are not strings are not ignored
+    let mut unbound =
+        sign_presentation(&presentation, &holder.sk, &holder_doc, None, None).unwrap();
+    verify_presentation(&unbound, &holder_doc, None, None).unwrap();
+    unbound["proof"]["challenge"] = json!(123);
+    assert!(matches!(
+        verify_presentation(&unbound, &holder_doc, None, None),
+        Err(PError::InvalidProof(_))
+    ));
+    // a credential proof cannot be used as a presentation proof
+    let proof = DataIntegrityProof::new(
+        &holder_doc.verification_method_id(holder_doc.verification_methods().next().unwrap()),
+        VerificationRelationship::AssertionMethod,
+    )
+    .with_created("2021-01-01T00:00:00Z");
+    let vp = proof.sign(&presentation, &holder.sk).unwrap();
+    assert_eq!(DataIntegrityProof::verify(&vp, &holder_doc).unwrap(), proof);
+    assert!(matches!(
+        verify_presentation(&vp, &holder_doc, None, None),
+        Err(PError::InvalidProof(_))
+    ));
+}
+
+#[test]
+fn jwk_import_export() {
+    use crate::keypair::convert_secret_to_jwk;
+    use crate::{
+        DidDocument, Jws, KeyPair, KeynumDerivation, PError, PublicKey, SecretKey,
+        VerificationRelationship,
+    };
+    use serde_json::json;
+    use std::io::Cursor;
+
+    // RFC 8037, appendices A.1 to A.3
+    let jwk = json!({
+        "kty": "OKP",
+        "crv": "Ed25519",
+        "d": "nWGxne_9WmC6hEr0kuwsxERJxWl7MmkZcDusAxyuf2A",
+        "x": "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"
+    });
+    let keynum = [1, 2, 3, 4, 5, 6, 7, 8];
+    let sk = SecretKey::from_jwk(&jwk, keynum).unwrap();
+    let pk = PublicKey::from_jwk(&jwk, keynum).unwrap();
+    assert_eq!(sk.public_key(), pk);
+    assert_eq!(sk.keynum(), keynum);
+    assert_eq!(
+        pk.jwk_thumbprint(),
+        "kPrK_qmxVWaYVA9wwBF6Iuo3vVzz7TxHCTwXBygrS4k"
+    );
+    assert_eq!(
+        pk.to_jwk(Some(&pk.jwk_thumbprint())),
+        json!({
+            "kty": "OKP",
+            "crv": "Ed25519",
+            "x": "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo",
+            "kid": "kPrK_qmxVWaYVA9wwBF6Iuo3vVzz7TxHCTwXBygrS4k"
+        })
+    );
+    assert_eq!(PublicKey::from_jwk(&pk.to_jwk(None), keynum).unwrap(), pk);
+    // the DID verification method of a JWK is named after its thumbprint
+    let doc = DidDocument::onion(&pk).unwrap();
+    let method = doc
+        .verification_methods_for(VerificationRelationship::Authentication)
+        .next()
+        .unwrap();
+    assert_eq!(method.fragment(), pk.jwk_thumbprint());
+
+    // the same key as generated from its seed, and signatures that verify
+    let seed =
+        data_encoding::base64url::decode_nopad(b"nWGxne_9WmC6hEr0kuwsxERJxWl7MmkZcDusAxyuf2A")
+            .unwrap();
+    let kp = KeyPair::generate_unencrypted_keypair(Some(seed)).unwrap();
+    assert_eq!(
+        Jws::sign(b"payload", &sk, None).unwrap(),
+        Jws::sign(b"payload", &kp.sk, None).unwrap()
+    );
+    let signature = crate::sign(Some(&pk), &sk, Cursor::new(b"data"), false, None, None).unwrap();
+    crate::verify(&pk, &signature, Cursor::new(b"data"), true, false).unwrap();
+
+    // round trip through convert_secret_to_jwk
+    let mut exported = vec![];
+    convert_secret_to_jwk(&mut exported, sk.clone()).unwrap();
+    let exported: serde_json::Value = serde_json::from_slice(&exported).unwrap();
+    assert_eq!(SecretKey::from_jwk(&exported, keynum).unwrap(), sk);
+
+    let sk = SecretKey::from_jwk_with_keynum(&jwk, KeynumDerivation::PublicKeyHash).unwrap();
+    let pk = PublicKey::from_jwk(&jwk, [0u8; 8])
+        .unwrap()
+        .with_derived_keynum();
+    assert_eq!(sk.public_key().keynum(), pk.keynum());
+
+    let mut mismatch = jwk.clone();
+    mismatch["x"] = json!("O2onvM62pC1io6jQKm8Nc2UyFXcd4kOmOsBIoYtZ2ik");
+    assert!(matches!(
+        SecretKey::from_jwk(&mismatch, keynum),
+        Err(PError::InvalidJwk(_))
+    ));
+    let mut x25519 = jwk.clone();
+    x25519["crv"] = json!("X25519");
+    assert!(matches!(
+        PublicKey::from_jwk(&x25519, keynum),
+        Err(PError::InvalidJwk(_))
+    ));
+    let mut short = jwk.clone();
+    short["d"] = json!("AAAA");
+    assert!(matches!(
+        SecretKey::from_jwk(&short, keynum),
+        Err(PError::InvalidSeedLength(3))
+    ));
+    assert!(matches!(
+        SecretKey::from_jwk(&pk.to_jwk(None), keynum),
+        Err(PError::MissingField("d"))
+    ));
+}
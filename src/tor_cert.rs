@@ -0,0 +1,263 @@
+use crate::base64::{Base64, Decoder, Encoder};
+use crate::constants::*;
+use crate::crypto::ed25519;
+use crate::errors::*;
+use crate::public_key::*;
+use crate::secret_key::*;
+use std::convert::TryFrom;
+use std::io::{Cursor, Read};
+
+const CERT_VERSION: u8 = 0x01;
+const CERT_KEY_TYPE_ED25519: u8 = 0x01;
+const EXT_SIGNED_WITH_ED25519_KEY: u8 = 0x04;
+const EXT_FLAG_AFFECTS_VALIDATION: u8 = 0x01;
+const PEM_BEGIN: &str = "-----BEGIN ED25519 CERT-----";
+const PEM_END: &str = "-----END ED25519 CERT-----";
+
+/// The purpose of a Tor ed25519 certificate, as listed in cert-spec.txt.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TorCertType {
+    /// A descriptor signing key, certified by the blinded onion service key.
+    HsV3DescSigning,
+    /// An introduction point authentication key, certified by the descriptor signing key.
+    HsV3IntroAuth,
+    /// An introduction point encryption key, certified by the descriptor signing key.
+    HsV3NtorEnc,
+    /// Any other certificate type.
+    Other(u8),
+}
+
+impl TorCertType {
+    fn from_u8(cert_type: u8) -> TorCertType {
+        match cert_type {
+            0x08 => TorCertType::HsV3DescSigning,
+            0x09 => TorCertType::HsV3IntroAuth,
+            0x0b => TorCertType::HsV3NtorEnc,
+            other => TorCertType::Other(other),
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            TorCertType::HsV3DescSigning => 0x08,
+            TorCertType::HsV3IntroAuth => 0x09,
+            TorCertType::HsV3NtorEnc => 0x0b,
+            TorCertType::Other(other) => other,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct TorCertExtension {
+    ext_type: u8,
+    flags: u8,
+    data: Vec<u8>,
+}
+
+/// A Tor ed25519 certificate (cert-spec.txt, section 2.1).
+///
+/// Onion services use these to chain the keys of a descriptor back to the
+/// blinded key derived from their identity key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TorCert {
+    cert_type: TorCertType,
+    expiration_hours: u32,
+    certified_key: [u8; PUBLICKEY_BYTES],
+    extensions: Vec<TorCertExtension>,
+    signature: [u8; SIGNATURE_BYTES],
+}
+
+impl TorCert {
+    /// Certify `desc_signing_key` as a descriptor signing key, with the blinded key `blinded_sk`.
+    ///
+    /// `expiration` is a UNIX timestamp; Tor certificates only have a one-hour
+    /// resolution, so it is rounded down to the hour.
+    pub fn new_descriptor_signing(
+        desc_signing_key: &PublicKey,
+        blinded_sk: &SecretKey,
+        expiration: u64,
+    ) -> Result<TorCert> {
+        TorCert::new(
+            TorCertType::HsV3DescSigning,
+            desc_signing_key,
+            blinded_sk,
+            expiration,
+        )
+    }
+
+    /// Create a certificate of any type for `certified_key`, signed by `signing_sk`.
+    ///
+    /// The signing key is always included as a `signed-with-ed25519-key` extension.
+    pub fn new(
+        cert_type: TorCertType,
+        certified_key: &PublicKey,
+        signing_sk: &SecretKey,
+        expiration: u64,
+    ) -> Result<TorCert> {
+        let expiration_hours = u32::try_from(expiration / 3600)
+            .map_err(|_| PError::InvalidTorCert("expiration date out of range"))?;
+        let signing_pk = signing_sk.public_key();
+        let mut cert = TorCert {
+            cert_type,
+            expiration_hours,
+            certified_key: certified_key.keynum_pk.pk,
+            extensions: vec![TorCertExtension {
+                ext_type: EXT_SIGNED_WITH_ED25519_KEY,
+                flags: 0,
+                data: signing_pk.keynum_pk.pk.to_vec(),
+            }],
+            signature: [0u8; SIGNATURE_BYTES],
+        };
+        cert.signature = signing_sk.sign_raw(&cert.signed_bytes(), None);
+        Ok(cert)
+    }
+
+    /// The type of this certificate.
+    pub fn cert_type(&self) -> TorCertType {
+        self.cert_type
+    }
+
+    /// The expiration date of this certificate, as a UNIX timestamp.
+    pub fn expiration(&self) -> u64 {
+        u64::from(self.expiration_hours) * 3600
+    }
+
+    /// Returns `true` if the certificate has expired at the UNIX timestamp `now`.
+    pub fn is_expired_at(&self, now: u64) -> bool {
+        now >= self.expiration()
+    }
+
+    /// The raw ed25519 key certified by this certificate.
+    pub fn certified_key(&self) -> &[u8; PUBLICKEY_BYTES] {
+        &self.certified_key
+    }
+
+    /// The raw ed25519 key that signed this certificate, if it is included.
+    pub fn signing_key(&self) -> Option<&[u8]> {
+        self.extensions
+            .iter()
+            .find(|ext| ext.ext_type == EXT_SIGNED_WITH_ED25519_KEY)
+            .map(|ext| &ext.data[..])
+    }
+
+    /// Verify the signature of the certificate.
+    ///
+    /// If `signed_with` is provided, the certificate must have been signed by
+    /// that key. Otherwise, the key from the `signed-with-ed25519-key`
+    /// extension is used. The expiration date is not checked.
+    pub fn verify(&self, signed_with: Option<&PublicKey>) -> Result<()> {
+        let signing_key = match (signed_with, self.signing_key()) {
+            (Some(pk), Some(key)) if key != pk.keynum_pk.pk => {
+                return Err(PError::TorCertSignatureInvalid)
+            }
+            (Some(pk), _) => &pk.keynum_pk.pk[..],
+            (None, Some(key)) => key,
+            (None, None) => return Err(PError::InvalidTorCert("missing signing key")),
+        };
+        if self.extensions.iter().any(|ext| {
+            ext.ext_type != EXT_SIGNED_WITH_ED25519_KEY
+                && ext.flags & EXT_FLAG_AFFECTS_VALIDATION != 0
+        }) {
+            return Err(PError::InvalidTorCert("unsupported critical extension"));
+        }
+        if !ed25519::verify(&self.signed_bytes(), signing_key, &self.signature) {
+            return Err(PError::TorCertSignatureInvalid);
+        }
+        Ok(())
+    }
+
+    fn signed_bytes(&self) -> Vec<u8> {
+        let mut v = vec![CERT_VERSION, self.cert_type.to_u8()];
+        v.extend_from_slice(&self.expiration_hours.to_be_bytes());
+        v.push(CERT_KEY_TYPE_ED25519);
+        v.extend_from_slice(&self.certified_key);
+        v.push(self.extensions.len() as u8);
+        for ext in &self.extensions {
+            v.extend_from_slice(&(ext.data.len() as u16).to_be_bytes());
+            v.push(ext.ext_type);
+            v.push(ext.flags);
+            v.extend_from_slice(&ext.data);
+        }
+        v
+    }
+
+    /// Serialize the certificate.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut v = self.signed_bytes();
+        v.extend_from_slice(&self.signature);
+        v
+    }
+
+    /// Deserialize a certificate.
+    pub fn from_bytes(bytes: &[u8]) -> Result<TorCert> {
+        let truncated = |_| PError::InvalidTorCert("truncated certificate");
+        let mut buf = Cursor::new(bytes);
+        let mut header = [0u8; 7];
+        buf.read_exact(&mut header).map_err(truncated)?;
+        if header[0] != CERT_VERSION {
+            return Err(PError::InvalidTorCert("unsupported version"));
+        }
+        if header[6] != CERT_KEY_TYPE_ED25519 {
+            return Err(PError::InvalidTorCert("unsupported certified key type"));
+        }
+        let mut expiration_hours = [0u8; 4];
+        expiration_hours.copy_from_slice(&header[2..6]);
+        let mut certified_key = [0u8; PUBLICKEY_BYTES];
+        buf.read_exact(&mut certified_key).map_err(truncated)?;
+        let mut n_extensions = [0u8; 1];
+        buf.read_exact(&mut n_extensions).map_err(truncated)?;
+        let mut extensions = Vec::with_capacity(n_extensions[0] as usize);
+        for _ in 0..n_extensions[0] {
+            let mut ext_header = [0u8; 4];
+            buf.read_exact(&mut ext_header).map_err(truncated)?;
+            let mut data = vec![0u8; u16::from_be_bytes([ext_header[0], ext_header[1]]) as usize];
+            buf.read_exact(&mut data).map_err(truncated)?;
+            if ext_header[2] == EXT_SIGNED_WITH_ED25519_KEY && data.len() != PUBLICKEY_BYTES {
+                return Err(PError::InvalidTorCert("invalid signing key extension"));
+            }
+            extensions.push(TorCertExtension {
+                ext_type: ext_header[2],
+                flags: ext_header[3],
+                data,
+            });
+        }
+        let mut signature = [0u8; SIGNATURE_BYTES];
+        buf.read_exact(&mut signature).map_err(truncated)?;
+        if buf.position() as usize != bytes.len() {
+            return Err(PError::InvalidTorCert("trailing data"));
+        }
+        Ok(TorCert {
+            cert_type: TorCertType::from_u8(header[1]),
+            expiration_hours: u32::from_be_bytes(expiration_hours),
+            certified_key,
+            extensions,
+            signature,
+        })
+    }
+
+    /// Encode the certificate as a `BEGIN ED25519 CERT` object, as found in descriptors.
+    pub fn to_pem(&self) -> String {
+        let encoded = Base64::encode_to_string(self.to_bytes()).unwrap();
+        let mut s = String::new();
+        s.push_str(PEM_BEGIN);
+        s.push('\n');
+        for line in encoded.as_bytes().chunks(64) {
+            s.push_str(std::str::from_utf8(line).unwrap());
+            s.push('\n');
+        }
+        s.push_str(PEM_END);
+        s.push('\n');
+        s
+    }
+
+    /// Decode a `BEGIN ED25519 CERT` object.
+    pub fn from_pem(s: &str) -> Result<TorCert> {
+        let s = s.trim();
+        let encoded = s
+            .strip_prefix(PEM_BEGIN)
+            .and_then(|s| s.strip_suffix(PEM_END))
+            .ok_or(PError::InvalidTorCert("missing certificate delimiters"))?;
+        let encoded: String = encoded.split_whitespace().collect();
+        TorCert::from_bytes(&Base64::decode_to_vec(encoded)?)
+    }
+}
@@ -0,0 +1,173 @@
+use crate::errors::*;
+use crate::onion_address::*;
+use crate::secret_key::*;
+use base32::Alphabet;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use x25519_dalek::StaticSecret;
+use zeroize::Zeroizing;
+
+const AUTH_PREFIX: &str = "descriptor:x25519:";
+
+fn decode_x25519_key(b32: &str) -> Result<Zeroizing<[u8; 32]>> {
+    let decoded = Zeroizing::new(
+        base32::decode(Alphabet::RFC4648 { padding: false }, b32.trim())
+            .ok_or(PError::InvalidTorClientAuth("invalid base32 key"))?,
+    );
+    if decoded.len() != 32 {
+        return Err(PError::InvalidTorClientAuth("key must be 32 bytes long"));
+    }
+    let mut key = Zeroizing::new([0u8; 32]);
+    key.copy_from_slice(&decoded);
+    Ok(key)
+}
+
+fn encode_x25519_key(key: &[u8; 32]) -> Zeroizing<String> {
+    Zeroizing::new(base32::encode(Alphabet::RFC4648 { padding: false }, key))
+}
+
+/// The public key of an authorized client, as stored by an onion service
+/// in `authorized_clients/<name>.auth`.
+///
+/// The file format is `descriptor:x25519:<base32 public key>`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TorClientAuth {
+    public_key: [u8; 32],
+}
+
+impl TorClientAuth {
+    /// Create a client authorization entry for an x25519 public key.
+    pub fn new(public_key: [u8; 32]) -> TorClientAuth {
+        TorClientAuth { public_key }
+    }
+
+    /// Parse the content of a `.auth` file.
+    pub fn from_string(s: &str) -> Result<TorClientAuth> {
+        let b32 = s
+            .trim()
+            .strip_prefix(AUTH_PREFIX)
+            .ok_or(PError::InvalidTorClientAuth("expected descriptor:x25519:"))?;
+        Ok(TorClientAuth {
+            public_key: *decode_x25519_key(b32)?,
+        })
+    }
+
+    /// Load a `.auth` file.
+    pub fn from_file<P>(path: P) -> Result<TorClientAuth>
+    where
+        P: AsRef<Path>,
+    {
+        TorClientAuth::from_string(&fs::read_to_string(path)?)
+    }
+
+    /// The x25519 public key of the client.
+    pub fn public_key(&self) -> &[u8; 32] {
+        &self.public_key
+    }
+}
+
+impl fmt::Display for TorClientAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            AUTH_PREFIX,
+            encode_x25519_key(&self.public_key).as_str()
+        )
+    }
+}
+
+/// The credentials of a client for an onion service, as stored by the client
+/// in `<ClientOnionAuthDir>/<name>.auth_private`.
+///
+/// The file format is `<onion address>:descriptor:x25519:<base32 secret key>`,
+/// the onion address being written without the `.onion` suffix.
+#[derive(Clone)]
+pub struct TorClientAuthPrivate {
+    onion_address: OnionAddress,
+    secret_key: Zeroizing<[u8; 32]>,
+}
+
+impl TorClientAuthPrivate {
+    /// Derive the credentials for the onion service `hostname` from a `SecretKey`.
+    ///
+    /// `hostname` is parsed as an `OnionAddress`.
+    pub fn new(
+        hostname: &str,
+        secret: &SecretKey,
+        derivation: X25519Derivation,
+    ) -> Result<TorClientAuthPrivate> {
+        let x25519_sk = StaticSecret::from(*secret.to_x25519(derivation)?);
+        Ok(TorClientAuthPrivate {
+            onion_address: hostname.parse()?,
+            secret_key: Zeroizing::new(x25519_sk.to_bytes()),
+        })
+    }
+
+    /// Parse the content of a `.auth_private` file.
+    pub fn from_string(s: &str) -> Result<TorClientAuthPrivate> {
+        let (hostname, b32) = s
+            .trim()
+            .split_once(':')
+            .ok_or(PError::InvalidTorClientAuth("missing onion address"))?;
+        let b32 = b32
+            .strip_prefix(AUTH_PREFIX)
+            .ok_or(PError::InvalidTorClientAuth("expected descriptor:x25519:"))?;
+        Ok(TorClientAuthPrivate {
+            onion_address: hostname.parse()?,
+            secret_key: decode_x25519_key(b32)?,
+        })
+    }
+
+    /// Load a `.auth_private` file.
+    pub fn from_file<P>(path: P) -> Result<TorClientAuthPrivate>
+    where
+        P: AsRef<Path>,
+    {
+        let s = Zeroizing::new(fs::read_to_string(path)?);
+        TorClientAuthPrivate::from_string(&s)
+    }
+
+    /// The address of the onion service.
+    pub fn onion_address(&self) -> &OnionAddress {
+        &self.onion_address
+    }
+
+    /// The x25519 secret key of the client.
+    pub fn secret_key(&self) -> &[u8; 32] {
+        &self.secret_key
+    }
+
+    /// The matching entry for the `authorized_clients` directory of the service.
+    pub fn to_client_auth(&self) -> TorClientAuth {
+        let public_key = x25519_dalek::PublicKey::from(&StaticSecret::from(*self.secret_key));
+        TorClientAuth {
+            public_key: public_key.to_bytes(),
+        }
+    }
+
+    /// Returns `true` if `auth` is the public counterpart of these credentials.
+    pub fn matches(&self, auth: &TorClientAuth) -> bool {
+        &self.to_client_auth() == auth
+    }
+
+    /// Serialize the credentials in the `.auth_private` format.
+    pub fn to_string(&self) -> Zeroizing<String> {
+        Zeroizing::new(format!(
+            "{}:{}{}",
+            self.onion_address.without_suffix(),
+            AUTH_PREFIX,
+            encode_x25519_key(&self.secret_key).as_str()
+        ))
+    }
+}
+
+impl fmt::Debug for TorClientAuthPrivate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TorClientAuthPrivate")
+            .field("onion_address", &format_args!("{}", self.onion_address))
+            .field("secret_key", &format_args!("<redacted>"))
+            .finish()
+    }
+}
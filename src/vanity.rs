@@ -0,0 +1,219 @@
+use crate::errors::*;
+use crate::helpers::*;
+use crate::keypair::*;
+use crate::public_key::*;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const ONION_ALPHABET: &str = "abcdefghijklmnopqrstuvwxyz234567";
+// the following characters also encode the checksum and the version
+const ONION_KEY_CHARS: usize = 51;
+const KEYNUM_HEX_LEN: usize = 16;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Progress of a running `VanitySearch`, passed to the progress callback.
+#[derive(Clone, Copy, Debug)]
+pub struct VanityProgress {
+    /// Key pairs tried so far, by all threads.
+    pub attempts: u64,
+    /// The average number of attempts needed to find a match.
+    pub expected_attempts: f64,
+    /// Time since the search started.
+    pub elapsed: Duration,
+}
+
+impl VanityProgress {
+    /// Key pairs tried per second.
+    pub fn rate(&self) -> f64 {
+        self.attempts as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// A handle to stop a `VanitySearch`, from another thread or from the progress callback.
+///
+/// A search cancelled before it starts returns at once. Call `reset()` to
+/// run a cancelled search again.
+#[derive(Clone, Debug, Default)]
+pub struct VanityCancel(Arc<AtomicBool>);
+
+impl VanityCancel {
+    /// Ask the search to stop; `VanitySearch::run()` then returns `None`.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if `cancel()` has been called.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Withdraw a cancellation, so that the search can be run again.
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Stops the workers when dropped, even if the progress callback panics.
+struct StopGuard<'a>(&'a AtomicBool);
+
+impl Drop for StopGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Multi-threaded search for a key pair with a recognizable onion address
+/// and/or key identifier.
+///
+/// Every attempt is a fresh random key pair, so each character of an onion
+/// address prefix multiplies the expected work by 32, and each character of
+/// a key identifier prefix by 16.
+#[derive(Clone, Debug)]
+pub struct VanitySearch {
+    onion_prefix: Option<String>,
+    keynum_prefix: Option<String>,
+    threads: usize,
+    cancel: VanityCancel,
+}
+
+impl VanitySearch {
+    /// Create a new search.
+    ///
+    /// # Arguments
+    ///
+    /// * `onion_prefix` - the onion address must start with these base32 characters (case-insensitive), at most 51 of them
+    /// * `keynum_prefix` - the key identifier, in hex as printed in `PublicKey::to_box()`, must start with these characters (case-insensitive)
+    ///
+    /// The search uses all the available CPUs, see `threads()`.
+    pub fn new(onion_prefix: Option<&str>, keynum_prefix: Option<&str>) -> Result<VanitySearch> {
+        let onion_prefix = onion_prefix.map(str::to_ascii_lowercase);
+        if let Some(prefix) = &onion_prefix {
+            if !prefix.chars().all(|c| ONION_ALPHABET.contains(c)) {
+                return Err(PError::InvalidVanityPattern(
+                    "onion prefix must only contain a-z and 2-7",
+                ));
+            }
+            if prefix.len() > ONION_KEY_CHARS {
+                return Err(PError::InvalidVanityPattern("onion prefix is too long"));
+            }
+        }
+        let keynum_prefix = keynum_prefix.map(str::to_ascii_uppercase);
+        if let Some(prefix) = &keynum_prefix {
+            if !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(PError::InvalidVanityPattern(
+                    "key identifier prefix must be hexadecimal",
+                ));
+            }
+            if prefix.len() > KEYNUM_HEX_LEN {
+                return Err(PError::InvalidVanityPattern(
+                    "key identifier prefix is too long",
+                ));
+            }
+            // the key identifier is printed without leading zeros
+            if prefix.starts_with('0') {
+                return Err(PError::InvalidVanityPattern(
+                    "key identifier prefix cannot start with 0",
+                ));
+            }
+        }
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        Ok(VanitySearch {
+            onion_prefix,
+            keynum_prefix,
+            threads,
+            cancel: VanityCancel::default(),
+        })
+    }
+
+    /// Set the number of worker threads.
+    pub fn threads(mut self, threads: usize) -> VanitySearch {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// The average number of key pairs to try before finding a match.
+    pub fn expected_attempts(&self) -> f64 {
+        let onion_len = self.onion_prefix.as_ref().map_or(0, String::len);
+        let keynum_len = self.keynum_prefix.as_ref().map_or(0, String::len);
+        32f64.powi(onion_len as i32) * 16f64.powi(keynum_len as i32)
+    }
+
+    /// A handle to cancel the search.
+    pub fn cancel_handle(&self) -> VanityCancel {
+        self.cancel.clone()
+    }
+
+    /// Returns `true` if `pk` matches the prefixes of this search.
+    pub fn is_match(&self, pk: &PublicKey) -> bool {
+        if let Some(prefix) = &self.keynum_prefix {
+            let keynum = format!("{:X}", load_u64_le(&pk.keynum_pk.keynum));
+            if !keynum.starts_with(prefix.as_str()) {
+                return false;
+            }
+        }
+        if let Some(prefix) = &self.onion_prefix {
+            if !pk
+                .to_onion_address()
+                .without_suffix()
+                .starts_with(prefix.as_str())
+            {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Run the search until a matching key pair is found, or the search is cancelled.
+    ///
+    /// `progress` is called about twice per second from the calling thread.
+    /// Returns `None` if the search was cancelled. The key pair is unencrypted.
+    pub fn run<F>(&self, mut progress: F) -> Result<Option<KeyPair>>
+    where
+        F: FnMut(&VanityProgress),
+    {
+        let stop = AtomicBool::new(false);
+        let attempts = AtomicU64::new(0);
+        let expected_attempts = self.expected_attempts();
+        let start = Instant::now();
+        let (tx, rx) = mpsc::channel();
+        thread::scope(|scope| {
+            for _ in 0..self.threads {
+                let tx = tx.clone();
+                let (stop, attempts) = (&stop, &attempts);
+                scope.spawn(move || {
+                    while !stop.load(Ordering::Relaxed) && !self.cancel.is_cancelled() {
+                        let keypair = match KeyPair::generate_unencrypted_keypair(None) {
+                            Ok(keypair) => keypair,
+                            Err(err) => {
+                                let _ = tx.send(Err(err));
+                                return;
+                            }
+                        };
+                        attempts.fetch_add(1, Ordering::Relaxed);
+                        if self.is_match(&keypair.pk) {
+                            let _ = tx.send(Ok(keypair));
+                            return;
+                        }
+                    }
+                });
+            }
+            drop(tx);
+            let _stop = StopGuard(&stop);
+            loop {
+                match rx.recv_timeout(PROGRESS_INTERVAL) {
+                    Ok(res) => break res.map(Some),
+                    Err(RecvTimeoutError::Timeout) => progress(&VanityProgress {
+                        attempts: attempts.load(Ordering::Relaxed),
+                        expected_attempts,
+                        elapsed: start.elapsed(),
+                    }),
+                    // every worker has stopped without a match
+                    Err(RecvTimeoutError::Disconnected) => break Ok(None),
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,74 @@
+use crate::constants::*;
+use crate::errors::*;
+use crate::helpers::*;
+use crate::signature_box::*;
+
+/// The outcome of a successful signature verification.
+///
+/// All the information exposed here has been authenticated, with the
+/// exception of the untrusted comment, which is deliberately omitted. The
+/// key identifier is not covered by the signature: it is only reported when
+/// it was checked against a trusted public key.
+#[derive(Clone, Debug)]
+pub struct VerificationReport {
+    pub(crate) keynum: Option<[u8; KEYNUM_BYTES]>,
+    pub(crate) sig_alg: [u8; TWOBYTES],
+    pub(crate) is_prehashed: bool,
+    pub(crate) trusted_comment: Option<String>,
+    pub(crate) timestamp: Option<u64>,
+}
+
+impl VerificationReport {
+    pub(crate) fn new(signature_box: &SignatureBox) -> Result<VerificationReport> {
+        let trusted_comment = match signature_box.sig_and_trusted_comment {
+            Some(_) => Some(signature_box.trusted_comment()?),
+            None => None,
+        };
+        let timestamp = trusted_comment.as_deref().and_then(parse_timestamp);
+        Ok(VerificationReport {
+            keynum: Some(signature_box.get_keynum()),
+            sig_alg: signature_box.get_sig_alg(),
+            is_prehashed: signature_box.is_prehashed(),
+            trusted_comment,
+            timestamp,
+        })
+    }
+
+    /// The key identifier used to create the signature, unless the signature
+    /// was verified without one, e.g. with `verify_onion()`.
+    pub fn keynum(&self) -> Option<&[u8]> {
+        self.keynum.as_ref().map(|keynum| &keynum[..])
+    }
+
+    /// The key identifier, as the number displayed in public key boxes.
+    pub fn key_id(&self) -> Option<u64> {
+        self.keynum.as_ref().map(|keynum| load_u64_le(keynum))
+    }
+
+    /// The signature algorithm (`Ed` or `ED`).
+    pub fn sig_alg(&self) -> [u8; TWOBYTES] {
+        self.sig_alg
+    }
+
+    /// Returns `true` if the signed data was pre-hashed.
+    pub fn is_prehashed(&self) -> bool {
+        self.is_prehashed
+    }
+
+    /// The verified trusted comment, if the signature had one.
+    pub fn trusted_comment(&self) -> Option<&str> {
+        self.trusted_comment.as_deref()
+    }
+
+    /// The `timestamp:` field of the trusted comment, if present.
+    pub fn timestamp(&self) -> Option<u64> {
+        self.timestamp
+    }
+}
+
+fn parse_timestamp(trusted_comment: &str) -> Option<u64> {
+    trusted_comment
+        .split('\t')
+        .find_map(|field| field.trim().strip_prefix("timestamp:"))
+        .and_then(|timestamp| timestamp.trim().parse().ok())
+}